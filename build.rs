@@ -1,4 +1,52 @@
-use std::process::Command;
+use std::{env, fs, path::PathBuf, process::Command};
+
+/// cpio "newc" archive holding nothing but the trailer entry
+fn empty_initramfs() -> Vec<u8> {
+    let name = b"TRAILER!!!\0";
+    // ino, mode, uid, gid, nlink, mtime, filesize, devmajor, devminor, rdevmajor, rdevminor,
+    // namesize, check
+    let fields = [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, name.len(), 0];
+    let mut archive = b"070701".to_vec();
+    for field in fields {
+        archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    archive.extend_from_slice(name);
+    archive.resize((archive.len() + 3) & !3, 0);
+    archive
+}
+
+/// Kernel symbol table from `nm -n -C` output, see `common::symbols` for the layout
+fn symbol_table(nm_output: &str) -> Vec<u8> {
+    let symbols: Vec<(u64, &str)> = nm_output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let addr = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let kind = fields.next()?;
+            let name = fields.next()?;
+            if kind != "t" && kind != "T" {
+                return None;
+            }
+            // Legacy mangling leaves the hash as the last path segment
+            let name = match name.rsplit_once("::h") {
+                Some((path, hash)) if hash.len() == 16 => path,
+                _ => name,
+            };
+            Some((addr, name))
+        })
+        .collect();
+
+    let mut table = (symbols.len() as u64).to_le_bytes().to_vec();
+    let mut names = Vec::new();
+    for (addr, name) in &symbols {
+        table.extend_from_slice(&addr.to_le_bytes());
+        table.extend_from_slice(&(names.len() as u32).to_le_bytes());
+        table.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        names.extend_from_slice(name.as_bytes());
+    }
+    table.extend_from_slice(&names);
+    table
+}
 
 fn main() {
     let output = Command::new("git")
@@ -13,4 +61,34 @@ fn main() {
     if let Some(log_level) = option_env!("LOG_LEVEL") {
         println!("cargo:rustc-env=LOG_LEVEL={}", log_level);
     }
+
+    // INITRAMFS points to a cpio archive, e.g. `find . | cpio -o -H newc > initramfs.cpio`
+    let initramfs = match env::var("INITRAMFS") {
+        Ok(path) => {
+            let path = fs::canonicalize(path).expect("INITRAMFS archive");
+            println!("cargo:rerun-if-changed={}", path.display());
+            path
+        }
+        Err(_) => {
+            let path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("initramfs.cpio");
+            fs::write(&path, empty_initramfs()).unwrap();
+            path
+        }
+    };
+    println!("cargo:rustc-env=INITRAMFS_PATH={}", initramfs.display());
+    println!("cargo:rerun-if-env-changed=INITRAMFS");
+
+    // KERNEL_SYMBOLS points to `nm -n -C` output of a previous link, see build.sh
+    let nm_output = match env::var("KERNEL_SYMBOLS") {
+        Ok(path) => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::read_to_string(path).expect("KERNEL_SYMBOLS file")
+        }
+        Err(_) => String::new(),
+    };
+    let symbols = PathBuf::from(env::var("OUT_DIR").unwrap()).join("kernel_symbols.bin");
+    fs::write(&symbols, symbol_table(&nm_output)).unwrap();
+    println!("cargo:rustc-env=KERNEL_SYMBOLS_PATH={}", symbols.display());
+    println!("cargo:rerun-if-env-changed=KERNEL_SYMBOLS");
+    println!("cargo:rerun-if-changed=src");
 }
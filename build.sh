@@ -1,5 +1,10 @@
-export RUSTFLAGS="-C target-cpu=cortex-a53 -C link-arg=-Tsrc/bsp/rpi3/link.ld -C link-arg=-otarget/kernel.elf -C relocation-model=pic"
+export RUSTFLAGS="-C target-cpu=cortex-a53 -C link-arg=-Tsrc/bsp/rpi3/link.ld -C link-arg=-otarget/kernel.elf -C relocation-model=static -C force-frame-pointers=yes"
 
 cargo rustc --target=aarch64-unknown-none-softfloat --release --features=rpi3 --no-default-features
 
+# Second pass embeds symbols of the first one, symbol table is linked after everything else
+# so addresses stay the same
+rust-nm -n -C --defined-only target/kernel.elf > target/kernel.sym
+KERNEL_SYMBOLS=target/kernel.sym cargo rustc --target=aarch64-unknown-none-softfloat --release --features=rpi3 --no-default-features
+
 rust-objcopy -O binary target/kernel.elf kernel8.img
@@ -0,0 +1,70 @@
+use core::{arch::asm, mem::size_of, ops::Range, ptr};
+
+/// Walks stop after this many frames, in case a corrupted chain loops within stack bounds
+const MAX_FRAMES: usize = 32;
+
+/// AAPCS64 frame record, x29 points at the one of the running function
+#[repr(C)]
+struct FrameRecord {
+    fp: usize,
+    lr: usize,
+}
+
+/// Return addresses found by following frame records, a record outside of `stacks` ends the
+/// walk. Requires the kernel to be built with `-C force-frame-pointers=yes`.
+pub struct FrameWalker<'a> {
+    fp: usize,
+    stacks: &'a [Range<usize>],
+    frames: usize,
+}
+
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp;
+    unsafe { asm!("mov {}, x29", out(reg) fp) };
+    fp
+}
+
+impl<'a> FrameWalker<'a> {
+    pub fn new(fp: usize, stacks: &'a [Range<usize>]) -> Self {
+        Self {
+            fp,
+            stacks,
+            frames: 0,
+        }
+    }
+
+    fn record_in_bounds(&self) -> bool {
+        let end = match self.fp.checked_add(size_of::<FrameRecord>()) {
+            Some(end) => end,
+            None => return false,
+        };
+
+        self.fp % 8 == 0
+            && self
+                .stacks
+                .iter()
+                .any(|stack| stack.start <= self.fp && end <= stack.end)
+    }
+}
+
+impl Iterator for FrameWalker<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.frames == MAX_FRAMES || !self.record_in_bounds() {
+            return None;
+        }
+
+        let record = unsafe { ptr::read(self.fp as *const FrameRecord) };
+        self.frames += 1;
+        // Records of callers are higher up the stack, anything else is garbage
+        self.fp = if record.fp > self.fp { record.fp } else { 0 };
+
+        if record.lr == 0 {
+            None
+        } else {
+            Some(record.lr)
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use bitaccess::ReadBits;
 use derive_more::Display;
 
 use crate::arch::{
@@ -43,6 +44,10 @@ pub fn mask_irq() {
     Daifset::write(Daifset::Irq)
 }
 
+pub fn is_irq_masked() -> bool {
+    Daif.read(Daif::IRQ).value() != 0
+}
+
 pub fn local_irq_save() -> u64 {
     Daif::new().get()
 }
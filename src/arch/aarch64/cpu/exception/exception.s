@@ -90,7 +90,7 @@ __exception_vector_addr:
     HANDLE_WITH_CONTEXT current_el1t_serror 1
 
 .org 0x200
-    HANDLE_WITH_CONTEXT current_el1h_sync 1
+    b el1_sync
 .org 0x280
     b el1_irq
 .org 0x300
@@ -99,7 +99,7 @@ __exception_vector_addr:
     HANDLE_WITH_CONTEXT current_el1h_serror 1
 
 .org 0x400
-    HANDLE_WITH_CONTEXT lower_aarch64_sync 0
+    b el0_sync
 .org 0x480
     b el0_irq
 .org 0x500
@@ -118,6 +118,12 @@ __exception_vector_addr:
 
 .org 0x800
 
+el1_sync:
+    KERNEL_ENTRY 1
+    mov x0, sp
+    bl current_el1h_sync
+    KERNEL_EXIT 1
+
 el1_irq:
     KERNEL_ENTRY 1
     bl current_el1h_irq
@@ -128,6 +134,12 @@ el0_irq:
     bl lower_aarch64_irq
     KERNEL_EXIT 0
 
+el0_sync:
+    KERNEL_ENTRY 0
+    mov x0, sp
+    bl lower_aarch64_sync
+    KERNEL_EXIT 0
+
 __ex_restore:
     ldr w19,      [sp, #16 * 16]
     ldp lr,  x20, [sp, #16 * 15]
@@ -164,12 +176,8 @@ return_from_fork:
     cbz x19, return_to_user
     mov x0, x20
     blr x19
+    bl task_exit
 
 return_to_user:
     bl mask_irq
-    KERNEL_EXIT 1
-
-return_from_syscall:
-    bl mask_irq
-    str x0, [sp, #0]
     KERNEL_EXIT 0
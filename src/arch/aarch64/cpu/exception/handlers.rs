@@ -1,23 +1,85 @@
+use bitaccess::ReadBits;
+
 use crate::{
     arch::arch_impl::cpu::{
-        exception::ExceptionContext,
-        registers::{esr_el1::EsrEl1, far_el1::FarEl1},
+        exception::{
+            asynchronous::{mask_irq, unmask_irq},
+            ExceptionContext,
+        },
+        registers::{
+            esr_el1::{EsrEl1, ExceptionClass, ISSDataAbort},
+            far_el1::FarEl1,
+        },
     },
     common::{
-        exception::asynchronous::{IRQContext, IRQManager},
+        exception::{
+            asynchronous::{IRQContext, IRQManager},
+            PrivilegeLevel,
+        },
+        memory::{
+            fault::{handle_page_fault, AccessKind, PageFault, FAULT_EXIT_CODE},
+            Address,
+        },
+        scheduler::{task_exit, SCHEDULER},
         statics,
+        syscall::{self, SysCallArgs},
     },
 };
 
-unsafe fn default_handler(kind: &'static str, e: &mut ExceptionContext) {
+unsafe fn default_handler(kind: &'static str, e: &mut ExceptionContext) -> ! {
     let far_el1 = FarEl1::new().get();
     let esr_el1 = EsrEl1::fetch();
     panic!(
-        "CPU Exception `{}`\n{}FAR_EL1:  {:#018x}\nESR_EL1:\n{}",
-        kind, e, far_el1, esr_el1,
+        "CPU Exception `{}`: {}\n{}FAR_EL1:  {:#018x}\nESR_EL1:\n{}",
+        kind,
+        esr_el1.report(far_el1, e.from_el0()),
+        e,
+        far_el1,
+        esr_el1,
     )
 }
 
+/// Resolves the fault or kills offending user task, faults in kernel code are fatal
+unsafe fn handle_abort(
+    kind: &'static str,
+    e: &mut ExceptionContext,
+    class: ExceptionClass,
+    level: PrivilegeLevel,
+) {
+    let esr_el1 = EsrEl1::fetch();
+    let iss = ISSDataAbort::from_value(esr_el1.read(EsrEl1::ISS).value());
+    let access = match class {
+        ExceptionClass::InstructionAbortLowerEL | ExceptionClass::InstructionAbortCurrentEL => {
+            AccessKind::Execute
+        }
+        _ if iss.is_write() => AccessKind::Write,
+        _ => AccessKind::Read,
+    };
+
+    let fault = PageFault {
+        addr: Address::new(FarEl1::new().get() as usize),
+        kind: iss.fault_kind(),
+        access,
+        level,
+    };
+
+    if let Err(err) = handle_page_fault(&fault) {
+        let report = esr_el1.report(fault.addr.addr() as u64, e.from_el0());
+        if fault.level != PrivilegeLevel::User {
+            crate::error!("{}: {}", report, err);
+            default_handler(kind, e)
+        }
+
+        crate::warn!(
+            "killing task {}: {}: {}",
+            SCHEDULER.current_pid(),
+            report,
+            err
+        );
+        task_exit(FAULT_EXIT_CODE)
+    }
+}
+
 #[no_mangle]
 unsafe extern "C" fn current_el1t_sync(_e: &mut ExceptionContext) {
     panic!("unsupported exception")
@@ -35,7 +97,13 @@ unsafe extern "C" fn current_el1t_serror(_e: &mut ExceptionContext) {
 
 #[no_mangle]
 unsafe extern "C" fn current_el1h_sync(e: &mut ExceptionContext) {
-    default_handler("current_el1h_sync", e)
+    match EsrEl1::fetch().exception_class() {
+        // Kernel touches user memory that was not paged in yet
+        Some(class @ ExceptionClass::DataAbortCurrentEL) => {
+            handle_abort("current_el1h_sync", e, class, PrivilegeLevel::Kernel)
+        }
+        _ => default_handler("current_el1h_sync", e),
+    }
 }
 
 #[no_mangle]
@@ -51,7 +119,25 @@ unsafe extern "C" fn current_el1h_serror(e: &mut ExceptionContext) {
 
 #[no_mangle]
 unsafe extern "C" fn lower_aarch64_sync(e: &mut ExceptionContext) {
-    default_handler("lower_aarch64_sync", e)
+    match EsrEl1::fetch().exception_class() {
+        Some(ExceptionClass::SVC64) => handle_svc(e),
+        Some(
+            class @ (ExceptionClass::DataAbortLowerEL | ExceptionClass::InstructionAbortLowerEL),
+        ) => handle_abort("lower_aarch64_sync", e, class, PrivilegeLevel::User),
+        _ => default_handler("lower_aarch64_sync", e),
+    }
+}
+
+/// Syscall number is passed in x8, arguments in x0-x5 and result is returned in x0
+unsafe fn handle_svc(e: &mut ExceptionContext) {
+    let mut args: SysCallArgs = [0; 6];
+    args.copy_from_slice(&e.registers[0..6]);
+
+    unmask_irq();
+    let ret = syscall::dispatch(e.registers[8], &args);
+    mask_irq();
+
+    e.registers[0] = ret;
 }
 
 #[no_mangle]
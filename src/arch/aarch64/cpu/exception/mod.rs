@@ -10,7 +10,7 @@ use crate::{
         aarch64::cpu::registers::current_el::current_el,
         arch_impl::cpu::registers::current_el::ExceptionLevel,
     },
-    common::exception::PrivilegeLevel,
+    common::{backtrace::Backtrace, exception::PrivilegeLevel},
 };
 
 pub mod asynchronous;
@@ -47,7 +47,31 @@ impl fmt::Display for ExceptionContext {
             self.spsr_el1 >> 16 & 0xff,
             self.spsr_el1 >> 8 & 0xff,
             self.spsr_el1 & 0xff
-        )
+        )?;
+
+        match self.backtrace() {
+            Some(backtrace) => write!(f, "{}", backtrace),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ExceptionContext {
+    pub fn from_el0(&self) -> bool {
+        // SPSR_EL1.M[3:0] is zero when exception was taken from EL0
+        self.spsr_el1 & 0b1111 == 0
+    }
+
+    /// Frames of interrupted kernel code, user frame pointers aren't trusted
+    pub fn backtrace(&self) -> Option<Backtrace> {
+        if self.from_el0() {
+            return None;
+        }
+
+        Some(Backtrace::new(
+            self.elr_el1 as usize,
+            self.registers[29] as usize,
+        ))
     }
 }
 
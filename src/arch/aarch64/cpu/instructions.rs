@@ -4,6 +4,10 @@ pub unsafe fn wfe() {
     asm!("wfe")
 }
 
+pub unsafe fn wfi() {
+    asm!("wfi")
+}
+
 pub unsafe fn nop() {
     asm!("nop")
 }
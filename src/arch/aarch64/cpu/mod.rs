@@ -1,30 +1,56 @@
-use core::arch::asm;
+use core::{
+    arch::{asm, global_asm},
+    cell::UnsafeCell,
+};
 
 use registers::mpidr_el1::core_id_el1;
 
 use crate::{
-    arch::arch_impl::cpu::{
-        instructions::{eret, wfe},
-        registers::{
-            cnthctl_el2::CnthctlEl2,
-            cntvoff_el2::CntvoffEl2,
-            current_el::{current_el, ExceptionLevel},
-            elr_el2::ElrEl2,
-            hcr_el2::HcrEl2,
-            sp_el1::SpEl1,
-            spsr_el2::SpsrEl2,
+    arch::arch_impl::{
+        cpu::{
+            instructions::{eret, wfe},
+            registers::{
+                cnthctl_el2::CnthctlEl2,
+                cntvoff_el2::CntvoffEl2,
+                current_el::{current_el, ExceptionLevel},
+                elr_el2::ElrEl2,
+                hcr_el2::HcrEl2,
+                sp_el1::SpEl1,
+                spsr_el2::SpsrEl2,
+            },
         },
+        memory::mmu::{boot_table_base_addr, enter_higher_half},
     },
     bsp::device::{
         cpu::BOOT_CORE_ID,
-        memory::{boot_core_stack_ende, bss},
+        memory::{
+            boot_core_stack_ende,
+            bss,
+            core_stack_ende,
+            mmu::KERNEL_VIRT_OFFSET,
+            SECONDARY_CORE_STACK_SLOT_SIZE,
+        },
+    },
+    common::{
+        memory::{mmu::MemoryManagementUnit, Address, Physical, Virtual},
+        statics,
     },
 };
 
+pub mod backtrace;
 pub mod exception;
 pub mod instructions;
 pub mod registers;
 
+global_asm!(
+    include_str!("secondary_start.s"),
+    stack_slot_size = const SECONDARY_CORE_STACK_SLOT_SIZE,
+);
+
+extern "Rust" {
+    static _start_secondary: UnsafeCell<()>;
+}
+
 #[no_mangle]
 unsafe fn _start() -> ! {
     if current_el() != ExceptionLevel::EL2 {
@@ -44,22 +70,64 @@ unsafe fn _start() -> ! {
 
 #[inline(always)]
 unsafe fn prepare_kernel() -> ! {
-    enter_el1()
+    enter_el1(boot_core_stack_ende(), boot_el1)
 }
 
-/// Method prepares register values for el2 -> el1 change and then entries `init` function
+/// Method prepares register values for el2 -> el1 change and then entries `el1_entry` with
+/// `stack_ende` as its stack
 #[inline(always)]
-unsafe fn enter_el1() -> ! {
+unsafe fn enter_el1(stack_ende: Address<Virtual>, el1_entry: unsafe fn() -> !) -> ! {
     CnthctlEl2::new().set(0b11);
     CntvoffEl2::new().set(0);
     HcrEl2::new().set(1 << 31); // Zero hcr_el2 register and set RW to EL1AArch64
     SpsrEl2::new().set(0b111100101);
-    ElrEl2::new().set(crate::kernel_init as *const () as u64);
-    SpEl1::new().set(boot_core_stack_ende().addr() as u64);
+    ElrEl2::new().set(el1_entry as *const () as u64);
+    SpEl1::new().set(stack_ende.addr() as u64);
 
     eret()
 }
 
+/// Runs from physical addresses, just like `_start`, so it only turns on the MMU and jumps to
+/// `kernel_init` in the high half
+unsafe fn boot_el1() -> ! {
+    if statics::MMU
+        .enable_mmu_and_caching(boot_table_base_addr())
+        .is_err()
+    {
+        park()
+    }
+
+    enter_higher_half(crate::kernel_init)
+}
+
+/// Physical address secondary cores have to be released into
+pub fn secondary_core_entry() -> Address<Physical> {
+    Address::new(unsafe { _start_secondary.get() as usize } - KERNEL_VIRT_OFFSET)
+}
+
+/// Called from `_start_secondary` on the stack of `core_id`
+#[no_mangle]
+unsafe extern "C" fn secondary_start(core_id: u64) -> ! {
+    if current_el() != ExceptionLevel::EL2 {
+        park()
+    }
+
+    enter_el1(core_stack_ende(core_id), secondary_el1)
+}
+
+/// Boot tables are still in place, so secondary cores take the same path into the high half
+/// as the boot core
+unsafe fn secondary_el1() -> ! {
+    if statics::MMU
+        .enable_mmu_and_caching(boot_table_base_addr())
+        .is_err()
+    {
+        park()
+    }
+
+    enter_higher_half(crate::secondary_core_init)
+}
+
 #[no_mangle]
 pub unsafe fn park() -> ! {
     loop {
@@ -2,6 +2,10 @@ use core::fmt;
 
 use bitaccess::{bitaccess, FieldAccess, ReadBits};
 use derive_more::Display;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::common::memory::fault::FaultKind;
 
 #[bitaccess(
     base_type = u64,
@@ -17,6 +21,66 @@ pub enum EsrEl1 {
     ISS,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, FromPrimitive)]
+pub enum ExceptionClass {
+    Unknown = 0b00_0000,
+    WFxTrap = 0b00_0001,
+    /// Access to FP/SIMD registers while `CPACR_EL1.FPEN` traps it
+    FpSimdTrap = 0b00_0111,
+    IllegalExecutionState = 0b00_1110,
+    SVC64 = 0b01_0101,
+    MsrMrsTrap = 0b01_1000,
+    InstructionAbortLowerEL = 0b10_0000,
+    InstructionAbortCurrentEL = 0b10_0001,
+    PCAlignment = 0b10_0010,
+    DataAbortLowerEL = 0b10_0100,
+    DataAbortCurrentEL = 0b10_0101,
+    SPAlignment = 0b10_0110,
+    FpException64 = 0b10_1100,
+    SError = 0b10_1111,
+    BreakpointLowerEL = 0b11_0000,
+    BreakpointCurrentEL = 0b11_0001,
+    SoftwareStepLowerEL = 0b11_0010,
+    SoftwareStepCurrentEL = 0b11_0011,
+    WatchpointLowerEL = 0b11_0100,
+    WatchpointCurrentEL = 0b11_0101,
+    BRK64 = 0b11_1100,
+}
+
+/// ISS of SVC and BRK, holds the immediate from the instruction
+#[bitaccess(base_type = u64, kind = read_only)]
+pub enum ISSImmediate {
+    #[bits(0..16)]
+    Imm16,
+}
+
+#[bitaccess(base_type = u64, kind = read_only)]
+pub enum ISSSError {
+    /// Remaining fields are implementation defined when set
+    #[bit(24)]
+    IDS,
+    /// Asynchronous error type
+    #[bits(10..13)]
+    AET,
+    #[bit(9)]
+    EA,
+    #[bits(0..6)]
+    DFSC,
+}
+
+/// Human readable summary of a synchronous exception or SError, e.g.
+/// "EL1 data abort: write to unmapped 0x... (level 3 translation fault)"
+pub struct ExceptionReport {
+    ec: u64,
+    iss: u64,
+    far: u64,
+    /// Classes which don't encode the exception level themselves rely on this
+    from_el0: bool,
+}
+
+/// Description of data and instruction abort fault status code
+struct FaultStatus(u64);
+
 #[bitaccess(base_type = u64, kind = read_only)]
 pub enum ISSDataAbort {
     #[bit(24)]
@@ -98,6 +162,170 @@ pub enum DfscVariants {
     ImplementationDefinedExclusive = 0x35,
 }
 
+impl EsrEl1Representation {
+    pub fn exception_class(&self) -> Option<ExceptionClass> {
+        ExceptionClass::from_u64(self.read(EsrEl1::EC).value())
+    }
+
+    /// `far` is only reported for classes which set FAR_EL1
+    pub fn report(&self, far: u64, from_el0: bool) -> ExceptionReport {
+        ExceptionReport {
+            ec: self.read(EsrEl1::EC).value(),
+            iss: self.read(EsrEl1::ISS).value(),
+            far,
+            from_el0,
+        }
+    }
+}
+
+impl ExceptionReport {
+    fn el(&self) -> &'static str {
+        if self.from_el0 {
+            "EL0"
+        } else {
+            "EL1"
+        }
+    }
+
+    fn imm16(&self) -> u64 {
+        ISSImmediate::from_value(self.iss)
+            .read(ISSImmediate::Imm16)
+            .value()
+    }
+
+    fn write_abort(&self, f: &mut fmt::Formatter<'_>, el: &str, instruction: bool) -> fmt::Result {
+        let iss = ISSDataAbort::from_value(self.iss);
+        let (kind, access) = match (instruction, iss.is_write()) {
+            (true, _) => ("instruction abort", "fetch from"),
+            (false, true) => ("data abort", "write to"),
+            (false, false) => ("data abort", "read from"),
+        };
+        let target = match iss.fault_kind() {
+            FaultKind::Translation => "unmapped ",
+            FaultKind::Permission => "protected ",
+            FaultKind::AccessFlag | FaultKind::Other => "",
+        };
+
+        write!(f, "{} {}: {} {}", el, kind, access, target)?;
+        if iss.read(ISSDataAbort::FnV).value() == 1 {
+            write!(f, "unknown address")?;
+        } else {
+            write!(f, "{:#018x}", self.far)?;
+        }
+        write!(
+            f,
+            " ({})",
+            FaultStatus(iss.read(ISSDataAbort::DFSC).value())
+        )
+    }
+
+    fn write_serror(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let iss = ISSSError::from_value(self.iss);
+        write!(f, "{} SError", self.el())?;
+        if iss.read(ISSSError::IDS).value() == 1 {
+            return write!(f, ": implementation defined syndrome {:#x}", self.iss);
+        }
+
+        let kind = match iss.read(ISSSError::AET).value() {
+            0b000 => "uncontainable",
+            0b001 => "unrecoverable state",
+            0b010 => "restartable state",
+            0b011 => "recoverable state",
+            0b110 => "corrected",
+            _ => "unknown error type",
+        };
+        write!(f, ": {}", kind)?;
+        if iss.read(ISSSError::EA).value() == 1 {
+            write!(f, ", external abort")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExceptionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let el = self.el();
+
+        match ExceptionClass::from_u64(self.ec) {
+            Some(ExceptionClass::SVC64) => write!(f, "{} svc #{:#x}", el, self.imm16()),
+            Some(ExceptionClass::BRK64) => write!(f, "{} brk #{:#x}", el, self.imm16()),
+            Some(ExceptionClass::InstructionAbortLowerEL) => self.write_abort(f, "EL0", true),
+            Some(ExceptionClass::InstructionAbortCurrentEL) => self.write_abort(f, "EL1", true),
+            Some(ExceptionClass::DataAbortLowerEL) => self.write_abort(f, "EL0", false),
+            Some(ExceptionClass::DataAbortCurrentEL) => self.write_abort(f, "EL1", false),
+            Some(ExceptionClass::PCAlignment) => {
+                write!(f, "{} PC alignment fault at {:#018x}", el, self.far)
+            }
+            Some(ExceptionClass::SPAlignment) => write!(f, "{} SP alignment fault", el),
+            Some(ExceptionClass::IllegalExecutionState) => {
+                write!(f, "{} illegal execution state, bad SPSR or ELR on eret", el)
+            }
+            Some(ExceptionClass::FpSimdTrap) => {
+                write!(f, "{} FP/SIMD access trapped by CPACR_EL1.FPEN", el)
+            }
+            Some(ExceptionClass::SError) => self.write_serror(f),
+            Some(ExceptionClass::Unknown) => write!(f, "{} unknown or undefined instruction", el),
+            Some(class) => write!(f, "{} {:?}, ISS {:#x}", el, class, self.iss),
+            None => write!(
+                f,
+                "{} unsupported exception class {:#08b}, ISS {:#x}",
+                el, self.ec, self.iss
+            ),
+        }
+    }
+}
+
+impl fmt::Display for FaultStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = (self.0 & 0b11) as i64;
+
+        match self.0 {
+            0x00..=0x03 => write!(f, "level {} address size fault", level),
+            0x04..=0x07 => write!(f, "level {} translation fault", level),
+            0x08..=0x0b => write!(f, "level {} access flag fault", level),
+            0x0c..=0x0f => write!(f, "level {} permission fault", level),
+            0x10 => write!(f, "synchronous external abort"),
+            0x11 => write!(f, "tag check fault"),
+            // 0x13 and 0x1b stand for level -1
+            0x13..=0x17 => write!(
+                f,
+                "external abort on level {} table walk",
+                self.0 as i64 - 0x14
+            ),
+            0x18 => write!(f, "parity or ECC error"),
+            0x1b..=0x1f => write!(
+                f,
+                "parity or ECC error on level {} table walk",
+                self.0 as i64 - 0x1c
+            ),
+            0x21 => write!(f, "alignment fault"),
+            0x29 => write!(f, "level -1 address size fault"),
+            0x2b => write!(f, "level -1 translation fault"),
+            0x30 => write!(f, "TLB conflict abort"),
+            0x31 => write!(f, "unsupported atomic hardware update"),
+            0x34 | 0x35 => write!(f, "implementation defined fault"),
+            status => write!(f, "unknown fault status {:#x}", status),
+        }
+    }
+}
+
+impl ISSDataAbort {
+    /// Instruction aborts encode fault status in the same bits as data aborts
+    pub fn fault_kind(&self) -> FaultKind {
+        match self.read(ISSDataAbort::DFSC).value() {
+            0x4..=0x7 | 0x2b => FaultKind::Translation,
+            0x8..=0xb => FaultKind::AccessFlag,
+            0xc..=0xf => FaultKind::Permission,
+            _ => FaultKind::Other,
+        }
+    }
+
+    pub fn is_write(&self) -> bool {
+        self.read(ISSDataAbort::WnR).value() == 1
+    }
+}
+
 impl fmt::Display for EsrEl1Representation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.read(EsrEl1::EC).value() {
@@ -0,0 +1,17 @@
+// Secondary cores released from the firmware spin table arrive here at EL2, with MMU off and
+// without a stack, so the stack has to be set up before any Rust code runs
+.section .text._start_secondary
+.global _start_secondary
+_start_secondary:
+    mrs x0, mpidr_el1
+    and x0, x0, #0b11
+
+    // Stack of core n ends n slots above the start of secondary core stacks, see `link.ld`
+    adrp x1, __secondary_core_stacks_start
+    add x1, x1, #:lo12:__secondary_core_stacks_start
+    mov x2, #{stack_slot_size}
+    madd x1, x0, x2, x1
+    mov sp, x1
+
+    bl secondary_start
+    b park
@@ -4,6 +4,7 @@ use crate::{
     arch::arch_impl::{
         cpu::registers::tcr_el1::{
             GranuleSize0,
+            GranuleSize1,
             IPSVariants,
             InnerCacheability,
             OuterCacheability,
@@ -14,16 +15,16 @@ use crate::{
             EPD1,
             TBI0,
         },
-        memory::mmu::translation_table::KernelTranslationTable,
+        memory::mmu::translation_table::{KernelTranslationTable, EMPTY_USER_TABLE},
     },
-    bsp::device::memory::mmu::KernelAddrSpace,
+    bsp::device::memory::mmu::{KernelAddrSpace, UserAddrSpace, KERNEL_VIRT_OFFSET},
     common::{
         memory::{
             mmu::{AddressSpace, MemoryManagementUnit, TranslationGranule},
             Address,
             Physical,
         },
-        sync::IRQSafeNullLock,
+        sync::IRQSpinLock,
     },
 };
 
@@ -35,9 +36,23 @@ pub struct Aarch64MemoryManagementUnit;
 pub type Granule512MB = TranslationGranule<{ 512 * 1024 * 1024 }>;
 pub type Granule64KB = TranslationGranule<{ 64 * 1024 }>;
 
+/// ASIDs are 8 bit wide, 0 is reserved for tasks without user space
+pub const NUM_ASIDS: usize = 256;
+
+#[link_section = ".data"]
+pub static KERNEL_TABLES: IRQSpinLock<KernelTranslationTable> =
+    IRQSpinLock::new(KernelTranslationTable::new());
+
+const NUM_BOOT_TABLE_ENTRIES: usize = KernelAddrSpace::SIZE >> Granule512MB::SHIFT;
+
+/// Level 2 table used by both TTBR0 and TTBR1 while kernel moves itself to the high half.
+/// Its only entry maps first 512MB of RAM, which contains whole kernel image.
+#[repr(C)]
+#[repr(align(128))]
+struct BootTranslationTable([u64; NUM_BOOT_TABLE_ENTRIES]);
+
 #[link_section = ".data"]
-pub static KERNEL_TABLES: IRQSafeNullLock<KernelTranslationTable> =
-    IRQSafeNullLock::new(KernelTranslationTable::new());
+static BOOT_TABLE: BootTranslationTable = BootTranslationTable::new();
 
 impl<const SIZE: usize> AddressSpace<SIZE> {
     pub const fn arch_address_space_size_sanity_checks() {
@@ -46,14 +61,77 @@ impl<const SIZE: usize> AddressSpace<SIZE> {
     }
 }
 
+impl BootTranslationTable {
+    const fn new() -> Self {
+        // Valid block, normal inner shareable memory, accessed, RW at EL1, never executable at EL0
+        const NORMAL_BLOCK: u64 = (1 << 54) | (1 << 10) | (0b11 << 8) | (mair::NORMAL << 2) | 0b01;
+
+        let mut entries = [0; NUM_BOOT_TABLE_ENTRIES];
+        entries[0] = NORMAL_BLOCK;
+
+        Self(entries)
+    }
+}
+
+/// Physical address of the boot table, valid only before MMU is enabled
+pub fn boot_table_base_addr() -> Address<Physical> {
+    Address::new(&BOOT_TABLE as *const _ as usize)
+}
+
+/// Moves stack and execution to the kernel link addresses. Has to be called with boot tables
+/// loaded, while running from physical addresses.
+pub unsafe fn enter_higher_half(f: unsafe fn() -> !) -> ! {
+    asm!(
+        "add sp, sp, {offset}",
+        "br {entry}",
+        offset = in(reg) KERNEL_VIRT_OFFSET,
+        entry = in(reg) f as usize + KERNEL_VIRT_OFFSET,
+        options(noreturn)
+    )
+}
+
+/// Loads user translation table of the next task, without user space TTBR0 points at an
+/// empty table so every EL0 address faults
+pub fn switch_user_table(table: Option<(Address<Physical>, u16)>) {
+    let ttbr0 = match table {
+        Some((base_addr, asid)) => base_addr.addr() as u64 | ((asid as u64) << 48),
+        None => EMPTY_USER_TABLE.base_addr().addr() as u64,
+    };
+
+    unsafe {
+        asm!("msr ttbr0_el1, {}", in(reg) ttbr0, options(nostack, nomem));
+        asm!("isb sy", options(nostack));
+    }
+}
+
+pub fn invalidate_tlb_asid(asid: u16) {
+    let asid = (asid as u64) << 48;
+    unsafe {
+        asm!("dsb ishst", options(nostack));
+        asm!("tlbi aside1is, {}", in(reg) asid, options(nostack));
+        asm!("dsb ish", options(nostack));
+        asm!("isb sy", options(nostack));
+    }
+}
+
+pub fn invalidate_tlb_page(addr: usize) {
+    let page = ((addr >> 12) & ((1 << 44) - 1)) as u64;
+    unsafe {
+        asm!("dsb ishst", options(nostack));
+        asm!("tlbi vaae1is, {}", in(reg) page, options(nostack));
+        asm!("dsb ish", options(nostack));
+        asm!("isb sy", options(nostack));
+    }
+}
+
 impl Aarch64MemoryManagementUnit {
     fn setup_mair(&self) {
         let mair_el1: u64 = 0b1111_1111_0000_0100;
         unsafe { asm!("msr mair_el1, {}", in(reg) mair_el1, options(nostack, nomem)) };
     }
 
-    fn configure_translation_control(&self) {
-        let t0sz = (64 - KernelAddrSpace::SHIFT) as u64;
+    fn configure_translation_control(&self, t0sz: u64) {
+        let t1sz = (64 - KernelAddrSpace::SHIFT) as u64;
 
         let mut val = TcrEl1::fetch();
         val.write_to_cache(TcrEl1::IPS, IPSVariants::Bits40);
@@ -68,10 +146,21 @@ impl Aarch64MemoryManagementUnit {
             TcrEl1::IRGN0,
             InnerCacheability::WriteBack_ReadAlloc_WriteAlloc,
         );
+        val.write_to_cache(TcrEl1::TG1, GranuleSize1::KB64);
+        val.write_to_cache(TcrEl1::SH1, Shareability::Inner);
+        val.write_to_cache(
+            TcrEl1::ORGN1,
+            OuterCacheability::WriteBack_ReadAlloc_WriteAlloc,
+        );
+        val.write_to_cache(
+            TcrEl1::IRGN1,
+            InnerCacheability::WriteBack_ReadAlloc_WriteAlloc,
+        );
         val.write_to_cache(TcrEl1::EPD0, EPD0::Enable);
         val.write_to_cache(TcrEl1::A1, A1::TTBR0);
-        val.write_to_cache(TcrEl1::EPD1, EPD1::Disable);
+        val.write_to_cache(TcrEl1::EPD1, EPD1::Enable);
         val.write_to_cache(TcrEl1::T0SZ, t0sz);
+        val.write_to_cache(TcrEl1::T1SZ, t1sz);
 
         TcrEl1::new().set(val.get());
     }
@@ -80,7 +169,7 @@ impl Aarch64MemoryManagementUnit {
 impl MemoryManagementUnit for Aarch64MemoryManagementUnit {
     unsafe fn enable_mmu_and_caching(
         &self,
-        translation_table_base_addr: Address<Physical>,
+        boot_table_base_addr: Address<Physical>,
     ) -> Result<(), &'static str> {
         if unlikely(self.is_enabled()) {
             return Err("MMU is already enabled");
@@ -94,10 +183,12 @@ impl MemoryManagementUnit for Aarch64MemoryManagementUnit {
 
         self.setup_mair();
 
-        let baddr: u64 = translation_table_base_addr.addr() as u64;
+        // Kernel keeps running from its load address until it jumps to the high half
+        let baddr: u64 = boot_table_base_addr.addr() as u64;
         asm!("msr ttbr0_el1, {}", in(reg) baddr, options(nostack, nomem));
+        asm!("msr ttbr1_el1, {}", in(reg) baddr, options(nostack, nomem));
 
-        self.configure_translation_control();
+        self.configure_translation_control((64 - KernelAddrSpace::SHIFT) as u64);
 
         asm!("isb sy");
 
@@ -112,6 +203,28 @@ impl MemoryManagementUnit for Aarch64MemoryManagementUnit {
         Ok(())
     }
 
+    unsafe fn switch_kernel_tables(
+        &self,
+        kernel_table_base_addr: Address<Physical>,
+    ) -> Result<(), &'static str> {
+        if unlikely(!self.is_enabled()) {
+            return Err("MMU is not enabled");
+        }
+
+        let baddr: u64 = kernel_table_base_addr.addr() as u64;
+        asm!("msr ttbr1_el1, {}", in(reg) baddr, options(nostack, nomem));
+        switch_user_table(None);
+
+        self.configure_translation_control((64 - UserAddrSpace::SHIFT) as u64);
+
+        asm!("isb sy");
+        asm!("tlbi vmalle1", options(nostack));
+        asm!("dsb ish", options(nostack));
+        asm!("isb sy");
+
+        Ok(())
+    }
+
     fn is_enabled(&self) -> bool {
         let sctlr_el1: u64;
         unsafe { asm!("mrs {}, sctlr_el1", out(reg) sctlr_el1, options(nostack, nomem)) };
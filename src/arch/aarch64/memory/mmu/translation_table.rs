@@ -7,33 +7,29 @@ use tock_registers::{
 };
 
 use crate::{
-    arch::arch_impl::memory::mmu::{mair, Granule512MB, Granule64KB},
+    arch::arch_impl::memory::mmu::{invalidate_tlb_page, mair, Granule512MB, Granule64KB},
     bsp::{
         device::memory::{
-            map::{user::LOW_MEMORY, END},
-            mmu::KernelAddrSpace,
+            map::END,
+            mmu::{KernelAddrSpace, UserAddrSpace, KERNEL_VIRT_OFFSET},
         },
-        rpi3::memory::{map::user::PAGE_COUNT, mmu::KernelGranule},
+        rpi3::memory::mmu::KernelGranule,
     },
-    common::{
-        memory::{
-            mmu::{
-                descriptors::{
-                    AccessPermissions,
-                    Attributes,
-                    Execute,
-                    MemoryAttributes,
-                    Page,
-                    PageSliceDescriptor,
-                },
-                translation_table::TranslationTable,
+    common::memory::{
+        mmu::{
+            descriptors::{
+                AccessPermissions,
+                Attributes,
+                Execute,
+                MemoryAttributes,
+                PageSliceDescriptor,
             },
-            Address,
-            Physical,
-            Virtual,
+            translation_table::TranslationTable,
         },
-        statics::KERNEL_TABLES,
-        sync::Mutex,
+        virt_to_phys,
+        Address,
+        Physical,
+        Virtual,
     },
 };
 
@@ -67,6 +63,11 @@ register_bitfields! {u64,
 
         OUTPUT_ADDR_64KB OFFSET(16) NUMBITS(32) [], // [47:16]
 
+        NG       OFFSET(11) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         AF       OFFSET(10) NUMBITS(1) [
             False = 0,
             True = 1
@@ -121,24 +122,41 @@ trait StartAddr {
 }
 
 const NUM_LVL2_TABLES: usize = KernelAddrSpace::SIZE >> Granule512MB::SHIFT;
+pub const NUM_USER_LVL2_TABLES: usize = UserAddrSpace::SIZE >> Granule512MB::SHIFT;
 
+/// Translation table covering `NUM_TABLES` * 512MB of virtual memory starting at `VA_BASE`.
+/// All zero bytes are a valid, uninitialized table.
 #[repr(C)]
 #[repr(align(65536))]
-pub struct FixedSizeTranslationTable<const NUM_TABLES: usize> {
+pub struct FixedSizeTranslationTable<const NUM_TABLES: usize, const VA_BASE: usize> {
     // 64 KB windows per page entry
     pub lvl3: [[PageDescriptor; 8192]; NUM_TABLES],
     // 512 MB descriptors
     lvl2: [TableDescriptor; NUM_TABLES],
-    current_l3_user_index: usize,
     current_l3_mmio_index: usize,
     is_initialized: bool,
 }
 
-pub type KernelTranslationTable = FixedSizeTranslationTable<NUM_LVL2_TABLES>;
+pub type KernelTranslationTable = FixedSizeTranslationTable<NUM_LVL2_TABLES, KERNEL_VIRT_OFFSET>;
+pub type UserTranslationTable = FixedSizeTranslationTable<NUM_USER_LVL2_TABLES, 0>;
+
+/// Level 2 table with no valid entries, loaded into TTBR0 when task has no user space
+#[repr(C)]
+#[repr(align(64))]
+pub struct EmptyTranslationTable([TableDescriptor; NUM_USER_LVL2_TABLES]);
+
+pub static EMPTY_USER_TABLE: EmptyTranslationTable =
+    EmptyTranslationTable([TableDescriptor::new_zeroed(); NUM_USER_LVL2_TABLES]);
 
 impl<T, const N: usize> StartAddr for [T; N] {
     fn start_addr(&self) -> Address<Physical> {
-        Address::new(self as *const _ as usize)
+        virt_to_phys(Address::new(self as *const _ as usize))
+    }
+}
+
+impl EmptyTranslationTable {
+    pub fn base_addr(&self) -> Address<Physical> {
+        self.0.start_addr()
     }
 }
 
@@ -179,17 +197,31 @@ impl convert::From<Attributes>
         desc += match attribute_fields.access {
             AccessPermissions::RX => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
             AccessPermissions::RW => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1,
+            AccessPermissions::RX_EL0 => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1_EL0,
             AccessPermissions::RW_EL0 => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1_EL0,
         };
 
-        desc += match attribute_fields.execute {
-            Execute::Allow => STAGE1_PAGE_DESCRIPTOR::PXN::False,
-            Execute::Never => STAGE1_PAGE_DESCRIPTOR::PXN::True,
+        // Code is executable either by kernel or by user, never by both
+        let user = matches!(
+            attribute_fields.access,
+            AccessPermissions::RX_EL0 | AccessPermissions::RW_EL0
+        );
+
+        desc += match (attribute_fields.execute, user) {
+            (Execute::Allow, false) => STAGE1_PAGE_DESCRIPTOR::PXN::False,
+            _ => STAGE1_PAGE_DESCRIPTOR::PXN::True,
+        };
+
+        desc += match (attribute_fields.execute, user) {
+            (Execute::Allow, true) => STAGE1_PAGE_DESCRIPTOR::UXN::False,
+            _ => STAGE1_PAGE_DESCRIPTOR::UXN::True,
         };
 
-        desc += match attribute_fields.execute {
-            Execute::Allow => STAGE1_PAGE_DESCRIPTOR::UXN::False,
-            Execute::Never => STAGE1_PAGE_DESCRIPTOR::UXN::True,
+        // User translations are tagged with ASID of their address space
+        desc += if user {
+            STAGE1_PAGE_DESCRIPTOR::NG::True
+        } else {
+            STAGE1_PAGE_DESCRIPTOR::NG::False
         };
 
         desc
@@ -220,9 +252,16 @@ impl PageDescriptor {
         InMemoryRegister::<u64, STAGE1_PAGE_DESCRIPTOR::Register>::new(self.value)
             .is_set(STAGE1_PAGE_DESCRIPTOR::VALID)
     }
+
+    pub fn output_addr(&self) -> Address<Physical> {
+        let shifted = InMemoryRegister::<u64, STAGE1_PAGE_DESCRIPTOR::Register>::new(self.value)
+            .read(STAGE1_PAGE_DESCRIPTOR::OUTPUT_ADDR_64KB);
+
+        Address::new((shifted as usize) << Granule64KB::SHIFT)
+    }
 }
 
-impl<const NUM_TABLES: usize> FixedSizeTranslationTable<NUM_TABLES> {
+impl<const NUM_TABLES: usize, const VA_BASE: usize> FixedSizeTranslationTable<NUM_TABLES, VA_BASE> {
     const L2_MMIO_START_INDEX: usize = NUM_TABLES - 1;
     const L3_MMIO_START_INDEX: usize = 8192 / 2;
 
@@ -235,13 +274,14 @@ impl<const NUM_TABLES: usize> FixedSizeTranslationTable<NUM_TABLES> {
             lvl3: [[PageDescriptor::new_zeroed(); 8192]; NUM_TABLES],
             lvl2: [TableDescriptor::new_zeroed(); NUM_TABLES],
             current_l3_mmio_index: 0,
-            current_l3_user_index: 0,
             is_initialized: false,
         }
     }
 
-    fn lvl2_lvl3_index_from(&self, page: &Page<Virtual>) -> Result<(usize, usize), &'static str> {
-        let addr = page.addr();
+    fn lvl2_lvl3_index_from(&self, addr: usize) -> Result<(usize, usize), &'static str> {
+        let addr = addr
+            .checked_sub(VA_BASE)
+            .ok_or("Virtual page out of bounds of translation table")?;
         let lvl2i = addr >> Granule512MB::SHIFT;
         let lvl3i = (addr & Granule512MB::MASK) >> Granule64KB::SHIFT;
 
@@ -252,10 +292,7 @@ impl<const NUM_TABLES: usize> FixedSizeTranslationTable<NUM_TABLES> {
         Ok((lvl2i, lvl3i))
     }
 
-    fn page_descriptor(
-        &mut self,
-        addr: &Page<Virtual>,
-    ) -> Result<&mut PageDescriptor, &'static str> {
+    fn page_descriptor(&mut self, addr: usize) -> Result<&mut PageDescriptor, &'static str> {
         let (lvl2i, lvl3i) = self.lvl2_lvl3_index_from(addr)?;
 
         Ok(&mut self.lvl3[lvl2i][lvl3i])
@@ -263,21 +300,23 @@ impl<const NUM_TABLES: usize> FixedSizeTranslationTable<NUM_TABLES> {
 
     fn mmio_start_addr(&self) -> Address<Virtual> {
         Address::new(
-            (Self::L2_MMIO_START_INDEX << Granule512MB::SHIFT)
+            VA_BASE + (Self::L2_MMIO_START_INDEX << Granule512MB::SHIFT)
                 | (Self::L3_MMIO_START_INDEX << Granule64KB::SHIFT),
         )
     }
 
     fn mmio_endi_addr(&self) -> Address<Virtual> {
         Address::new(
-            (Self::L2_MMIO_START_INDEX << Granule512MB::SHIFT)
+            VA_BASE + (Self::L2_MMIO_START_INDEX << Granule512MB::SHIFT)
                 | (8191 << Granule64KB::SHIFT)
                 | (Granule64KB::SIZE - 1),
         )
     }
 }
 
-impl<const NUM_TABLES: usize> TranslationTable for FixedSizeTranslationTable<NUM_TABLES> {
+impl<const NUM_TABLES: usize, const VA_BASE: usize> TranslationTable
+    for FixedSizeTranslationTable<NUM_TABLES, VA_BASE>
+{
     fn init(&mut self) {
         if self.is_initialized {
             panic!("Translation tables are already initialized");
@@ -321,7 +360,7 @@ impl<const NUM_TABLES: usize> TranslationTable for FixedSizeTranslationTable<NUM
         }
 
         for (ppage, vpage) in p.iter().zip(v.iter()) {
-            let descriptor = self.page_descriptor(vpage)?;
+            let descriptor = self.page_descriptor(vpage.addr())?;
             if descriptor.is_valid() {
                 crate::error!("{:x}, {:x}", ppage.addr(), vpage.addr());
                 return Err("map_pages: Virtual page already mapped");
@@ -351,46 +390,50 @@ impl<const NUM_TABLES: usize> TranslationTable for FixedSizeTranslationTable<NUM
         }
 
         let addr = Address::new(
-            (Self::L2_MMIO_START_INDEX << Granule512MB::SHIFT)
-                | (self.current_l3_mmio_index << Granule64KB::SHIFT),
+            VA_BASE
+                + ((Self::L2_MMIO_START_INDEX << Granule512MB::SHIFT)
+                    | (self.current_l3_mmio_index << Granule64KB::SHIFT)),
         );
         self.current_l3_mmio_index += num_pages;
 
         Ok(PageSliceDescriptor::from_addr(addr, num_pages))
     }
 
-    fn next_user_page_slice(
+    unsafe fn unmap_pages(
         &mut self,
-        num_pages: usize,
-    ) -> Result<PageSliceDescriptor<Virtual>, &'static str> {
+        vpages: PageSliceDescriptor<Virtual>,
+    ) -> Result<(), &'static str> {
         if !self.is_initialized {
-            return Err("next_page_slice: Translation table is uninitialized");
+            return Err("unmap_pages: Translation table is uninitialized");
         }
 
-        if num_pages == 0 {
-            return Err("next_page_slice: num_pages = 0");
-        }
+        let v = vpages.as_slice();
 
-        if (self.current_l3_user_index + num_pages) > PAGE_COUNT {
-            return Err("out of memory");
+        if v.iter().any(|vpage| {
+            self.lvl2_lvl3_index_from(vpage.addr())
+                .map(|(lvl2i, lvl3i)| !self.lvl3[lvl2i][lvl3i].is_valid())
+                .unwrap_or(true)
+        }) {
+            return Err("unmap_pages: Virtual page not mapped");
         }
 
-        let addr: usize = LOW_MEMORY.addr() + (self.current_l3_user_index * Granule64KB::SIZE);
-        self.current_l3_user_index += num_pages;
+        for vpage in v.iter() {
+            *self.page_descriptor(vpage.addr())? = PageDescriptor::new_zeroed();
+            invalidate_tlb_page(vpage.addr());
+        }
 
-        crate::trace!("registered {} user page(s) at {}", num_pages, addr);
+        Ok(())
+    }
 
-        let ppages = PageSliceDescriptor::from_addr(Address::<Physical>::new(addr), num_pages);
-        let vpages = PageSliceDescriptor::from_addr(Address::<Virtual>::new(addr), num_pages);
-        let attributes = Attributes {
-            memory: MemoryAttributes::CacheableDRAM,
-            access: AccessPermissions::RW_EL0,
-            execute: Execute::Never,
-        };
+    fn translate(&self, addr: Address<Virtual>) -> Result<Address<Physical>, &'static str> {
+        let (lvl2i, lvl3i) = self.lvl2_lvl3_index_from(addr.addr())?;
+        let descriptor = &self.lvl3[lvl2i][lvl3i];
 
-        unsafe { KERNEL_TABLES.map_locked(|kt| kt.map_pages(vpages, ppages, attributes))? };
+        if !descriptor.is_valid() {
+            return Err("translate: Virtual page not mapped");
+        }
 
-        Ok(vpages)
+        Ok(descriptor.output_addr() + (addr.addr() & Granule64KB::MASK))
     }
 
     fn is_page_slice_mmio(&self, pages: PageSliceDescriptor<Virtual>) -> bool {
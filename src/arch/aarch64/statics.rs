@@ -1,8 +1,8 @@
 use crate::{
     arch::arch_impl::{memory::mmu::Aarch64MemoryManagementUnit, time::GenericTimer},
-    common::sync::IRQSafeNullLock,
+    common::sync::IRQSpinLock,
 };
 
-pub static CLOCK_TIMER: IRQSafeNullLock<GenericTimer> = IRQSafeNullLock::new(GenericTimer);
+pub static CLOCK_TIMER: IRQSpinLock<GenericTimer> = IRQSpinLock::new(GenericTimer);
 pub static MMU: Aarch64MemoryManagementUnit = Aarch64MemoryManagementUnit;
 pub use super::memory::mmu::KERNEL_TABLES;
@@ -2,7 +2,20 @@ use core::arch::global_asm;
 
 global_asm!(include_str!("syscall.s"));
 
+// Errors are returned as negated `Errno` values
 extern "Rust" {
-    pub fn _exit(code: isize);
-    pub fn _write(start: *const u8, len: usize);
+    pub fn _exit(code: isize) -> !;
+    pub fn _write(fd: usize, start: *const u8, len: usize) -> isize;
+    pub fn _read(fd: usize, start: *mut u8, len: usize) -> isize;
+    pub fn _yield() -> isize;
+    pub fn _sleep(micros: u64) -> isize;
+    pub fn _getpid() -> isize;
+    pub fn _spawn(entry: usize, stack_top: usize) -> isize;
+    pub fn _wait(pid: u64, status: *mut i64) -> isize;
+    pub fn _mmap(len: usize) -> isize;
+    pub fn _munmap(start: usize, len: usize) -> isize;
+    pub fn _uptime() -> isize;
+    pub fn _open(path: *const u8, path_len: usize, flags: u64) -> isize;
+    pub fn _close(fd: usize) -> isize;
+    pub fn _lseek(fd: usize, offset: i64, whence: u64) -> isize;
 }
@@ -11,3 +11,75 @@ _write:
     mov w8, #1
     svc #0
     ret
+
+.global _read
+_read:
+    mov w8, #2
+    svc #0
+    ret
+
+.global _yield
+_yield:
+    mov w8, #3
+    svc #0
+    ret
+
+.global _sleep
+_sleep:
+    mov w8, #4
+    svc #0
+    ret
+
+.global _getpid
+_getpid:
+    mov w8, #5
+    svc #0
+    ret
+
+.global _spawn
+_spawn:
+    mov w8, #6
+    svc #0
+    ret
+
+.global _wait
+_wait:
+    mov w8, #7
+    svc #0
+    ret
+
+.global _mmap
+_mmap:
+    mov w8, #8
+    svc #0
+    ret
+
+.global _munmap
+_munmap:
+    mov w8, #9
+    svc #0
+    ret
+
+.global _uptime
+_uptime:
+    mov w8, #10
+    svc #0
+    ret
+
+.global _open
+_open:
+    mov w8, #11
+    svc #0
+    ret
+
+.global _close
+_close:
+    mov w8, #12
+    svc #0
+    ret
+
+.global _lseek
+_lseek:
+    mov w8, #13
+    svc #0
+    ret
@@ -31,6 +31,19 @@ pub struct PtRegs {
     pub pstate: u64,
 }
 
+impl PtRegs {
+    /// SPSR value for EL0 using SP_EL0, with all interrupts unmasked
+    const PSTATE_EL0T: u64 = 0b0000;
+
+    /// Prepares registers so that exception return lands at `pc` in EL0
+    pub fn move_to_user_mode(&mut self, pc: u64, sp: u64) {
+        self.registers = [0; 31];
+        self.pc = pc;
+        self.sp = sp;
+        self.pstate = Self::PSTATE_EL0T;
+    }
+}
+
 extern "C" {
     pub fn cpu_switch_to(prev: *const Task, next: *const Task);
 }
@@ -23,6 +23,26 @@ impl GenericTimer {
         unsafe { asm!("mrs {}, cntfrq_el0", out(reg) cntfrq_el0, options(nostack, nomem)) };
         cntfrq_el0
     }
+
+    fn wait_ticks(&self, ticks: u64) {
+        unsafe {
+            asm!("msr cntp_tval_el0, {}", in(reg) ticks, options(nostack, nomem));
+            let flags = 0b11_u64;
+            asm!("msr cntp_ctl_el0, {}", in(reg) flags, options(nostack, nomem));
+
+            loop {
+                let val: u64;
+                asm!("mrs {}, cntp_ctl_el0", out(reg) val, options(nostack, nomem));
+                // ISTATUS is set once the timer fires
+                if (val & 0b100) != 0 {
+                    break;
+                }
+            }
+
+            let flags = 0_u64;
+            asm!("msr cntp_ctl_el0, {}", in(reg) flags, options(nostack, nomem));
+        }
+    }
 }
 
 impl ClockManager for GenericTimer {
@@ -34,39 +54,23 @@ impl ClockManager for GenericTimer {
         Duration::from_nanos((self.cntpct_el0() * NS_IN_S) / self.cntfrq_el0())
     }
 
+    /// Busy waits on the physical timer, meant for early boot and drivers that need short
+    /// delays. Tasks should use `timer_queue::sleep` instead.
     fn sleep(&self, duration: Duration) {
         if duration.as_nanos() == 0 {
             return;
         }
 
-        let frq = self.cntfrq_el0();
-        let x = match frq.checked_mul(duration.as_nanos() as u64) {
-            None => todo!("Warn here"),
-            Some(val) => val,
-        };
-        let time = x / NS_IN_S;
+        // Round up, so durations below timer resolution still wait for one tick
+        let frq = u128::from(self.cntfrq_el0());
+        let ns_in_s = u128::from(NS_IN_S);
+        let mut remaining = (duration.as_nanos() * frq + ns_in_s - 1) / ns_in_s;
 
-        if time == 0 {
-            todo!("Warn here 0")
-        } else if time > u32::MAX as u64 {
-            todo!("Warn here max")
-        }
-
-        unsafe {
-            asm!("msr cntp_tval_el0, {}", in(reg) time, options(nostack, nomem));
-            let flags = 0b11_u64;
-            asm!("msr cntp_ctl_el0, {}", in(reg) flags, options(nostack, nomem));
-
-            loop {
-                let val: u64;
-                asm!("mrs {}, cntp_ctl_el0", out(reg) val, options(nostack, nomem));
-                if (val & 0b100) == 0 {
-                    break;
-                }
-            }
-
-            let flags = 0_u64;
-            asm!("msr cntp_ctl_el0, {}", in(reg) flags, options(nostack, nomem));
+        // cntp_tval_el0 is only 32 bits wide, longer waits are split into chunks
+        while remaining > 0 {
+            let chunk = remaining.min(u128::from(u32::MAX));
+            self.wait_ticks(chunk as u64);
+            remaining -= chunk;
         }
     }
 }
@@ -1,45 +1,40 @@
+use alloc::{format, sync::Arc, vec::Vec};
 use core::{
     sync::atomic::{AtomicUsize, Ordering},
     time::Duration,
 };
 
 use tock_registers::{
-    interfaces::{ReadWriteable, Writeable},
+    interfaces::{Readable, Writeable},
     register_bitfields,
     register_structs,
-    registers::ReadWrite,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
 };
 
 use crate::{
     bsp::device_driver::WrappedPointer,
     common::{
         driver::Driver,
+        fs::{devfs::DeviceNode, Inode, InodeKind},
         memory::mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
         statics::CLOCK_TIMER,
-        sync::{IRQSafeNullLock, Mutex},
+        sync::{IRQSpinLock, Mutex},
+        syscall::Errno,
         time::clock::ClockManager,
     },
 };
 
+const NUM_PINS: usize = 54;
+/// Pins routed to PL011, they are kept out of devfs so the console can't be broken
+const UART_PINS: [usize; 2] = [14, 15];
+/// PL011 CTS and RTS, used only with hardware flow control. Kept out of devfs as well, as flow
+/// control can be turned on while a node is open.
+const UART_FLOW_CONTROL_PINS: [usize; 2] = [16, 17];
+
 // TODO: Use custom macro
 register_bitfields! {
     u32,
 
-    // GPIO Function Select 1
-    GPFSEL1 [
-        FSEL15 OFFSET(15) NUMBITS(3) [
-            Input = 0b000,
-            Output = 0b001,
-            AltFunc0 = 0b100  // PL011 UART RX
-
-        ],
-        FSEL14 OFFSET(12) NUMBITS(3) [
-            Input = 0b000,
-            Output = 0b001,
-            AltFunc0 = 0b100  // PL011 UART TX
-        ]
-    ],
-
     /// GPIO Pull-up/down Register
     GPPUD [
         /// Controls the actuation of the internal pull-up/down control line to ALL the GPIO pins.
@@ -65,9 +60,15 @@ register_bitfields! {
 
 register_structs! {
     RegisterBlock {
-        (0x00 => _reserved1),
-        (0x04 => gpfsel1: ReadWrite<u32, GPFSEL1::Register>),
-        (0x08 => _reserved2),
+        /// GPIO Function Select 0-5, 3 bits per pin
+        (0x00 => gpfsel: [ReadWrite<u32>; 6]),
+        (0x18 => _reserved1),
+        (0x1C => gpset: [WriteOnly<u32>; 2]),
+        (0x24 => _reserved2),
+        (0x28 => gpclr: [WriteOnly<u32>; 2]),
+        (0x30 => _reserved3),
+        (0x34 => gplev: [ReadOnly<u32>; 2]),
+        (0x3C => _reserved4),
         (0x94 => gppud: ReadWrite<u32, GPPUD::Register>),
         (0x98 => gppudclk0: ReadWrite<u32, GPPUDCLK0::Register>),
         (0x9C => @END),
@@ -81,7 +82,22 @@ pub struct GpioInner {
 pub struct Gpio {
     mmio_descriptor: MMIODescriptor,
     virt_mmio_start_addr: AtomicUsize,
-    inner: IRQSafeNullLock<GpioInner>,
+    inner: IRQSpinLock<GpioInner>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Function {
+    Input = 0b000,
+    Output = 0b001,
+    AltFunc0 = 0b100,
+    AltFunc3 = 0b111,
+}
+
+/// Device file of a single pin. Reads return `0` or `1`, writing `0` or `1` drives the pin as
+/// an output and writing `in` turns it back into an input.
+struct GpioLine {
+    gpio: &'static Gpio,
+    pin: usize,
 }
 
 impl GpioInner {
@@ -114,10 +130,30 @@ impl GpioInner {
         self.registers.gppudclk0.set(0);
     }
 
+    pub fn set_function(&mut self, pin: usize, function: Function) {
+        let register = &self.registers.gpfsel[pin / 10];
+        let shift = (pin % 10) * 3;
+
+        register.set((register.get() & !(0b111 << shift)) | ((function as u32) << shift));
+    }
+
+    pub fn set_level(&mut self, pin: usize, high: bool) {
+        let bit = 1 << (pin % 32);
+        if high {
+            self.registers.gpset[pin / 32].set(bit);
+        } else {
+            self.registers.gpclr[pin / 32].set(bit);
+        }
+    }
+
+    pub fn level(&self, pin: usize) -> bool {
+        self.registers.gplev[pin / 32].get() & (1 << (pin % 32)) != 0
+    }
+
     pub fn map_pl011_uart(&mut self) {
-        self.registers
-            .gpfsel1
-            .modify(GPFSEL1::FSEL15::AltFunc0 + GPFSEL1::FSEL14::AltFunc0);
+        for pin in UART_PINS {
+            self.set_function(pin, Function::AltFunc0);
+        }
 
         self.disable_pud_14_15_bcm2837();
     }
@@ -128,9 +164,23 @@ impl Gpio {
         Self {
             mmio_descriptor,
             virt_mmio_start_addr: AtomicUsize::new(0),
-            inner: IRQSafeNullLock::new(GpioInner::new(mmio_descriptor.start_addr().addr())),
+            inner: IRQSpinLock::new(GpioInner::new(mmio_descriptor.start_addr().addr())),
         }
     }
+
+    pub fn map_pl011_flow_control(&self, enable: bool) {
+        let function = if enable {
+            Function::AltFunc3
+        } else {
+            Function::Input
+        };
+
+        self.inner.map_locked(|inner| {
+            for pin in UART_FLOW_CONTROL_PINS {
+                inner.set_function(pin, function);
+            }
+        })
+    }
 }
 
 impl Driver for Gpio {
@@ -155,6 +205,18 @@ impl Driver for Gpio {
         Ok(())
     }
 
+    fn device_nodes(&'static self) -> Vec<DeviceNode> {
+        (0..NUM_PINS)
+            .filter(|pin| !UART_PINS.contains(pin) && !UART_FLOW_CONTROL_PINS.contains(pin))
+            .map(|pin| {
+                DeviceNode::new(
+                    &format!("gpio{}", pin),
+                    Arc::new(GpioLine { gpio: self, pin }),
+                )
+            })
+            .collect()
+    }
+
     fn virt_mmio_start_addr(&self) -> Option<usize> {
         let addr = self.virt_mmio_start_addr.load(Ordering::Relaxed);
 
@@ -165,3 +227,38 @@ impl Driver for Gpio {
         }
     }
 }
+
+impl Inode for GpioLine {
+    fn kind(&self) -> InodeKind {
+        InodeKind::CharDevice
+    }
+
+    /// Level is reported once, so reads reach end of file
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        if offset > 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let level = self.gpio.inner.map_locked(|inner| inner.level(self.pin));
+        buf[0] = if level { b'1' } else { b'0' };
+
+        Ok(1)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, Errno> {
+        let pin = self.pin;
+        let value = buf.strip_suffix(b"\n").unwrap_or(buf);
+        self.gpio.inner.map_locked(|inner| {
+            match value {
+                b"0" | b"1" => {
+                    inner.set_level(pin, value == b"1");
+                    inner.set_function(pin, Function::Output);
+                }
+                b"in" => inner.set_function(pin, Function::Input),
+                _ => return Err(Errno::EINVAL),
+            }
+
+            Ok(buf.len())
+        })
+    }
+}
@@ -0,0 +1,181 @@
+use num_traits::{FromPrimitive, ToPrimitive};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+use crate::{
+    arch::arch_impl::cpu::registers::mpidr_el1::core_id_el1,
+    bsp::device_driver::{
+        bcm::bcm2xxx_interrupt_controller::{LocalIRQ, PendingIRQs},
+        WrappedPointer,
+    },
+    common::{
+        driver::Driver,
+        exception::asynchronous::{IRQContext, IRQDescriptor, IRQManager},
+        memory::mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
+        sync::{IRQSpinLock, Mutex, ReadWriteLock, RwSpinLock},
+    },
+    info,
+};
+
+/// Each of 4 cores has 4 mailboxes, mailbox `m` of core `n` is at index `4 * n + m`
+const MAILBOXES_PER_CORE: usize = 4;
+
+register_structs! {
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x50 => mailbox_control: [ReadWrite<u32>; 4]),
+        (0x60 => irq_source: [ReadOnly<u32>; 4]),
+        (0x70 => _reserved2),
+        (0x80 => mailbox_set: [WriteOnly<u32>; 16]),
+        (0xc0 => mailbox_clear: [ReadWrite<u32>; 16]),
+        (0x100 => @END),
+    }
+}
+
+type Registers = WrappedPointer<RegisterBlock>;
+type HandlerTable = [Option<(LocalIRQ, IRQDescriptor)>; LocalIRQ::len()];
+
+/// Per core interrupts of the BCM2836 ARM local block. Registers are banked by core number,
+/// so enabling and handling IRQs affects only the calling core.
+pub struct LocalInterruptController {
+    descriptor: MMIODescriptor,
+    registers: IRQSpinLock<Registers>,
+    handlers: RwSpinLock<HandlerTable>,
+}
+
+impl LocalInterruptController {
+    pub const unsafe fn new(descriptor: MMIODescriptor) -> Self {
+        Self {
+            descriptor,
+            registers: IRQSpinLock::new(Registers::new(descriptor.start_addr().addr())),
+            handlers: RwSpinLock::new([None; LocalIRQ::len()]),
+        }
+    }
+
+    fn pending(&self) -> PendingIRQs {
+        let core_id = unsafe { core_id_el1() } as usize;
+        let pending_mask = self
+            .registers
+            .map_locked(|registers| registers.irq_source[core_id].get());
+
+        PendingIRQs::new(u64::from(pending_mask))
+    }
+
+    /// Peripheral IRQs reach the core they're routed to, core 0 by default, as its GPU interrupt
+    pub fn is_gpu_pending(&self) -> bool {
+        self.pending()
+            .iter()
+            .any(|no| no == LocalIRQ::GPUInterrupt as usize)
+    }
+
+    /// Raises `mailbox` IRQ on core `core_id`
+    pub fn signal(&self, core_id: usize, mailbox: LocalIRQ) {
+        let idx = core_id * MAILBOXES_PER_CORE + mailbox_number(mailbox);
+        self.registers
+            .map_locked(|registers| registers.mailbox_set[idx].set(1))
+    }
+
+    pub fn print_status(&self) {
+        info!("  local IC:");
+        self.handlers.map_read(|handlers| {
+            let mut any = false;
+            for (irq, descriptor) in handlers.iter().flatten() {
+                info!(
+                    "    {}[{}] -> \"{}\"",
+                    irq,
+                    irq.to_u64().expect("irq to_u64"),
+                    descriptor.name
+                );
+                any = true;
+            }
+            if !any {
+                info!("    no handlers registered");
+            }
+        })
+    }
+}
+
+fn mailbox_number(mailbox: LocalIRQ) -> usize {
+    match mailbox {
+        LocalIRQ::Mailbox0 => 0,
+        LocalIRQ::Mailbox1 => 1,
+        LocalIRQ::Mailbox2 => 2,
+        LocalIRQ::Mailbox3 => 3,
+        _ => panic!("local IRQ {} is not a mailbox", mailbox),
+    }
+}
+
+impl IRQManager for LocalInterruptController {
+    type IRQNumberT = LocalIRQ;
+
+    fn register_handler(
+        &self,
+        irq: Self::IRQNumberT,
+        descriptor: IRQDescriptor,
+    ) -> Result<(), &'static str> {
+        self.handlers.map_write(|table| {
+            let no = irq.to_usize().expect("irq to_usize");
+            if table[no].is_some() {
+                return Err("Handler already registered");
+            }
+
+            table[no] = Some((irq, descriptor));
+
+            Ok(())
+        })
+    }
+
+    /// Enables `irq` on the calling core only, mailboxes are the only supported ones
+    fn enable(&self, irq: Self::IRQNumberT) {
+        let core_id = unsafe { core_id_el1() } as usize;
+        let enable_bit: u32 = 1 << mailbox_number(irq);
+        self.registers.map_locked(|registers| {
+            let control = &registers.mailbox_control[core_id];
+            control.set(control.get() | enable_bit)
+        })
+    }
+
+    /// Mailboxes are cleared before their handler runs, so a signal raised while it runs isn't
+    /// lost. GPU interrupt is left to the peripheral IC.
+    fn handle_pending<'ctx>(&'ctx self, _token: IRQContext<'ctx>) {
+        let core_id = unsafe { core_id_el1() } as usize;
+
+        for no in self.pending().iter() {
+            let irq = LocalIRQ::from_usize(no).expect("no to LocalIRQ");
+            match irq {
+                LocalIRQ::GPUInterrupt => continue,
+                LocalIRQ::Mailbox0
+                | LocalIRQ::Mailbox1
+                | LocalIRQ::Mailbox2
+                | LocalIRQ::Mailbox3 => {
+                    let idx = core_id * MAILBOXES_PER_CORE + mailbox_number(irq);
+                    self.registers
+                        .map_locked(|registers| registers.mailbox_clear[idx].set(u32::MAX));
+                }
+                _ => {}
+            }
+
+            match self.handlers.map_read(|table| table[no]) {
+                None => panic!("No handler for local IRQ {}", irq),
+                Some((_, d)) => d.handler.handle().expect("Handling local IRQ"),
+            }
+        }
+    }
+}
+
+impl Driver for LocalInterruptController {
+    fn compat(&self) -> &'static str {
+        "bcm local interrupt controller"
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        let addr = map_kernel_mmio(self.compat(), self.descriptor)?.addr();
+
+        self.registers.map_locked(|r| *r = Registers::new(addr));
+
+        Ok(())
+    }
+}
@@ -2,17 +2,24 @@ use derive_more::Display;
 use num_derive::{FromPrimitive, ToPrimitive};
 
 use crate::{
-    bsp::device_driver::bcm::bcm2xxx_interrupt_controller::peripheral_ic::PeripheralInterruptController,
+    bsp::device_driver::bcm::bcm2xxx_interrupt_controller::{
+        local_ic::LocalInterruptController,
+        peripheral_ic::PeripheralInterruptController,
+    },
     common::{
         driver::Driver,
-        exception::asynchronous::{IRQContext, IRQDescriptor, IRQManager},
+        exception::asynchronous::{IPIManager, IRQContext, IRQDescriptor, IRQManager},
         memory::mmu::descriptors::MMIODescriptor,
     },
     info,
 };
 
+mod local_ic;
 mod peripheral_ic;
 
+/// Mailbox used by cores to interrupt each other
+const IPI_MAILBOX: LocalIRQ = LocalIRQ::Mailbox0;
+
 struct PendingIRQs {
     bitmask: u64,
 }
@@ -21,7 +28,7 @@ struct PendingIRQsIter {
     bitmask: u64,
 }
 
-#[derive(FromPrimitive, ToPrimitive, Copy, Clone)]
+#[derive(FromPrimitive, ToPrimitive, Copy, Clone, Display)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum LocalIRQ {
     CNTPSIRQ = 0,
@@ -58,6 +65,12 @@ pub enum PeripheralIRQ {
     UARTInt = 57,
 }
 
+impl LocalIRQ {
+    pub const fn len() -> usize {
+        12
+    }
+}
+
 impl PeripheralIRQ {
     pub const fn len() -> usize {
         64
@@ -66,13 +79,13 @@ impl PeripheralIRQ {
 
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
-// TODO: When needed provide local IRQs
 pub enum IRQNumber {
     Local(LocalIRQ),
     Peripheral(PeripheralIRQ),
 }
 
 pub struct InterruptController {
+    local: LocalInterruptController,
     peripheral: PeripheralInterruptController,
 }
 
@@ -104,8 +117,9 @@ impl Iterator for PendingIRQsIter {
 }
 
 impl InterruptController {
-    pub const unsafe fn new(_local_mmio: MMIODescriptor, periph_mmio: MMIODescriptor) -> Self {
+    pub const unsafe fn new(local_mmio: MMIODescriptor, periph_mmio: MMIODescriptor) -> Self {
         Self {
+            local: LocalInterruptController::new(local_mmio),
             peripheral: PeripheralInterruptController::new(periph_mmio),
         }
     }
@@ -113,8 +127,7 @@ impl InterruptController {
     pub fn print_status(&self) {
         info!("interrupt controller:");
         self.peripheral.print_status();
-        info!("  local IC:");
-        info!("    WIP");
+        self.local.print_status();
     }
 }
 
@@ -124,7 +137,8 @@ impl Driver for InterruptController {
     }
 
     unsafe fn init(&self) -> Result<(), &'static str> {
-        self.peripheral.init()
+        self.peripheral.init()?;
+        self.local.init()
     }
 }
 
@@ -137,19 +151,36 @@ impl IRQManager for InterruptController {
         descriptor: IRQDescriptor,
     ) -> Result<(), &'static str> {
         match irq {
-            IRQNumber::Local(_) => unimplemented!("Local IRQ controller"),
+            IRQNumber::Local(irq) => self.local.register_handler(irq, descriptor),
             IRQNumber::Peripheral(irq) => self.peripheral.register_handler(irq, descriptor),
         }
     }
 
     fn enable(&self, irq: Self::IRQNumberT) {
         match irq {
-            IRQNumber::Local(_) => unimplemented!("Local IRQ controller"),
+            IRQNumber::Local(irq) => self.local.enable(irq),
             IRQNumber::Peripheral(irq) => self.peripheral.enable(irq),
         }
     }
 
     fn handle_pending<'ctx>(&'ctx self, token: IRQContext<'ctx>) {
-        self.peripheral.handle_pending(token)
+        if self.local.is_gpu_pending() {
+            self.peripheral.handle_pending(token);
+        }
+        self.local.handle_pending(token)
+    }
+}
+
+impl IPIManager for InterruptController {
+    fn register_ipi_handler(&self, descriptor: IRQDescriptor) -> Result<(), &'static str> {
+        self.local.register_handler(IPI_MAILBOX, descriptor)
+    }
+
+    fn enable_ipi(&self) {
+        self.local.enable(IPI_MAILBOX)
+    }
+
+    fn send_ipi(&self, core_id: usize) {
+        self.local.signal(core_id, IPI_MAILBOX)
     }
 }
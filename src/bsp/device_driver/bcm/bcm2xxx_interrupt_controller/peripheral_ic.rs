@@ -14,7 +14,7 @@ use crate::{
         driver::Driver,
         exception::asynchronous::{IRQContext, IRQDescriptor, IRQManager},
         memory::mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
-        sync::{IRQSafeNullLock, InitStateLock, Mutex, ReadWriteLock},
+        sync::{IRQSpinLock, Mutex, ReadWriteLock, RwSpinLock},
     },
     info,
 };
@@ -41,9 +41,9 @@ type HandlerTable = [Option<(PeripheralIRQ, IRQDescriptor)>; PeripheralIRQ::len(
 
 pub struct PeripheralInterruptController {
     descriptor: MMIODescriptor,
-    wo_registers: IRQSafeNullLock<WriteOnlyRegisters>,
-    ro_registers: InitStateLock<ReadOnlyRegisters>,
-    handlers: InitStateLock<HandlerTable>,
+    wo_registers: IRQSpinLock<WriteOnlyRegisters>,
+    ro_registers: IRQSpinLock<ReadOnlyRegisters>,
+    handlers: RwSpinLock<HandlerTable>,
 }
 
 impl PeripheralInterruptController {
@@ -51,14 +51,14 @@ impl PeripheralInterruptController {
         let addr = descriptor.start_addr().addr();
         Self {
             descriptor,
-            wo_registers: IRQSafeNullLock::new(WriteOnlyRegisters::new(addr)),
-            ro_registers: InitStateLock::new(ReadOnlyRegisters::new(addr)),
-            handlers: InitStateLock::new([None; PeripheralIRQ::len()]),
+            wo_registers: IRQSpinLock::new(WriteOnlyRegisters::new(addr)),
+            ro_registers: IRQSpinLock::new(ReadOnlyRegisters::new(addr)),
+            handlers: RwSpinLock::new([None; PeripheralIRQ::len()]),
         }
     }
 
     fn pending(&self) -> PendingIRQs {
-        let pending_mask: u64 = self.ro_registers.map_read(|ro_registers| {
+        let pending_mask: u64 = self.ro_registers.map_locked(|ro_registers| {
             u64::from(ro_registers.pending2.get()) << 32 | u64::from(ro_registers.pending1.get())
         });
 
@@ -118,15 +118,17 @@ impl IRQManager for PeripheralInterruptController {
         })
     }
 
+    /// Handlers run without the table locked, they may switch tasks
     fn handle_pending<'ctx>(&'ctx self, _token: IRQContext<'ctx>) {
-        self.handlers.map_read(|table| {
-            self.pending()
-                .iter()
-                .for_each(|no| match table[no.to_usize().expect("no to_usize")] {
-                    None => panic!("No handler for IRQ {}", no.to_u64().expect("no to_u64")),
-                    Some((_, ref d)) => d.handler.handle().expect("Handling IRQ"),
-                });
-        })
+        for no in self.pending().iter() {
+            let entry = self
+                .handlers
+                .map_read(|table| table[no.to_usize().expect("no to_usize")]);
+            match entry {
+                None => panic!("No handler for IRQ {}", no.to_u64().expect("no to_u64")),
+                Some((_, d)) => d.handler.handle().expect("Handling IRQ"),
+            }
+        }
     }
 }
 
@@ -141,7 +143,7 @@ impl Driver for PeripheralInterruptController {
         self.wo_registers
             .map_locked(|r| *r = WriteOnlyRegisters::new(addr));
         self.ro_registers
-            .map_write(|r| *r = ReadOnlyRegisters::new(addr));
+            .map_locked(|r| *r = ReadOnlyRegisters::new(addr));
 
         Ok(())
     }
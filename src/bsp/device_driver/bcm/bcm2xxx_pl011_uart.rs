@@ -1,18 +1,22 @@
+use alloc::{sync::Arc, vec, vec::Vec};
 use core::{
     fmt,
     fmt::Arguments,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
 use tock_registers::{
-    interfaces::{Readable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     register_bitfields,
     register_structs,
     registers::{ReadOnly, ReadWrite, WriteOnly},
 };
 
 use crate::{
-    arch::arch_impl::cpu::instructions::nop,
+    arch::arch_impl::cpu::{
+        exception::asynchronous::is_irq_masked,
+        instructions::{nop, wfi},
+    },
     bsp::device_driver::{
         bcm::bcm2xxx_interrupt_controller::{IRQNumber, PeripheralIRQ},
         WrappedPointer,
@@ -20,13 +24,18 @@ use crate::{
     common::{
         driver::Driver,
         exception::asynchronous::{IRQDescriptor, IRQHandler, IRQManager},
+        fs::{console::Console, devfs::DeviceNode},
         memory::mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
-        serial_console,
+        ring_buffer::RingBuffer,
+        serial_console::{self, LineConfig, Parity, StopBits},
         statics,
-        sync::{IRQSafeNullLock, Mutex},
+        sync::{IRQSpinLock, Mutex, WaitQueue},
     },
 };
 
+const RX_BUFFER_SIZE: usize = 1024;
+const TX_BUFFER_SIZE: usize = 4096;
+
 register_bitfields! {
     u32,
 
@@ -56,10 +65,33 @@ register_bitfields! {
         FEN  OFFSET(4) NUMBITS(1) [
             FifosDisabled = 0,
             FifosEnabled = 1
+        ],
+
+        STP2 OFFSET(3) NUMBITS(1) [
+            OneStopBit = 0,
+            TwoStopBits = 1
+        ],
+
+        EPS OFFSET(2) NUMBITS(1) [
+            OddParity = 0,
+            EvenParity = 1
+        ],
+
+        PEN OFFSET(1) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
         ]
     ],
 
     CR [
+        CTSEN OFFSET(15) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+        RTSEN OFFSET(14) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
         RXE OFFSET(9) NUMBITS(1) [
             Disabled = 0,
             Enabled = 1
@@ -75,7 +107,14 @@ register_bitfields! {
     ],
 
     IFLS [
-        RXIFLSEL OFFSET(3) NUMBITS(5) [
+        RXIFLSEL OFFSET(3) NUMBITS(3) [
+            OneEigth = 0b000,
+            OneQuarter = 0b001,
+            OneHalf = 0b010,
+            ThreeQuarters = 0b011,
+            SevenEights = 0b100
+        ],
+        TXIFLSEL OFFSET(0) NUMBITS(3) [
             OneEigth = 0b000,
             OneQuarter = 0b001,
             OneHalf = 0b010,
@@ -89,6 +128,10 @@ register_bitfields! {
             Disabled = 0,
             Enabled = 1
         ],
+        TXIM OFFSET(5) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
         RXIM OFFSET(4) NUMBITS(1) [
             Disabled = 0,
             Enabled = 1
@@ -97,6 +140,7 @@ register_bitfields! {
 
     MIS [
         RTMIS OFFSET(6) NUMBITS(1) [],
+        TXMIS OFFSET(5) NUMBITS(1) [],
         RXMIS OFFSET(4) NUMBITS(1) []
     ],
 
@@ -127,21 +171,91 @@ register_structs! {
 
 pub struct PL011UartInner {
     registers: WrappedPointer<RegisterBlock>,
+    /// Reference clock in Hz, baud rate divisors are derived from it
+    clock: u32,
+    config: LineConfig,
 }
 
 pub struct PL011Uart {
     mmio_descriptor: MMIODescriptor,
     virt_mmio_start_addr: AtomicUsize,
-    inner: IRQSafeNullLock<PL011UartInner>,
+    inner: IRQSpinLock<PL011UartInner>,
+    /// Bytes drained from RX FIFO by the IRQ handler, newest ones are dropped when it's full
+    rx_buffer: IRQSpinLock<RingBuffer<u8, RX_BUFFER_SIZE>>,
+    rx_waiters: WaitQueue,
+    /// Bytes waiting for space in TX FIFO, drained by the TX interrupt
+    tx_buffer: IRQSpinLock<RingBuffer<u8, TX_BUFFER_SIZE>>,
+    /// TX interrupts are delivered, until then output is written synchronously
+    tx_irq_enabled: AtomicBool,
 }
 
 impl PL011UartInner {
-    pub const unsafe fn new(start: usize) -> Self {
+    pub const unsafe fn new(start: usize, clock: u32, config: LineConfig) -> Self {
         Self {
             registers: WrappedPointer::new(start),
+            clock,
+            config,
         }
     }
 
+    /// Baud rate divisor is `clock / (16 * baud)`, with 6 fractional bits
+    fn divisors(&self, baud: u32) -> Result<(u32, u32), &'static str> {
+        if baud == 0 {
+            return Err("baud rate can't be zero");
+        }
+
+        let divisor = (self.clock as u64 * 4 + baud as u64 / 2) / baud as u64;
+        let (integer, fraction) = (divisor >> 6, divisor & 0x3f);
+        if integer == 0 || integer > 0xffff || (integer == 0xffff && fraction != 0) {
+            return Err("baud rate not supported by the UART clock");
+        }
+
+        Ok((integer as u32, fraction as u32))
+    }
+
+    /// Programs line settings, UART has to be disabled while they change
+    fn apply_config(&mut self, config: LineConfig) -> Result<(), &'static str> {
+        let (integer, fraction) = self.divisors(config.baud)?;
+        let word_length = match config.word_length {
+            5 => LCR_H::WLEN::Five,
+            6 => LCR_H::WLEN::Six,
+            7 => LCR_H::WLEN::Seven,
+            8 => LCR_H::WLEN::Eight,
+            _ => return Err("word length has to be between 5 and 8"),
+        };
+        let parity = match config.parity {
+            Parity::None => LCR_H::PEN::Disabled,
+            Parity::Even => LCR_H::PEN::Enabled + LCR_H::EPS::EvenParity,
+            Parity::Odd => LCR_H::PEN::Enabled + LCR_H::EPS::OddParity,
+        };
+        let stop_bits = match config.stop_bits {
+            StopBits::One => LCR_H::STP2::OneStopBit,
+            StopBits::Two => LCR_H::STP2::TwoStopBits,
+        };
+        let flow_control = if config.flow_control {
+            CR::CTSEN::Enabled + CR::RTSEN::Enabled
+        } else {
+            CR::CTSEN::Disabled + CR::RTSEN::Disabled
+        };
+
+        self.flush();
+        self.registers.cr.set(0);
+
+        self.registers.ibrd.write(IBRD::BAUD_DIVINT.val(integer));
+        self.registers.fbrd.write(FBRD::BAUD_DIVFRAC.val(fraction));
+        // Divisors are latched by LCR_H write
+        self.registers
+            .lcr_h
+            .write(LCR_H::FEN::FifosEnabled + word_length + parity + stop_bits);
+
+        self.registers
+            .cr
+            .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled + flow_control);
+        self.config = config;
+
+        Ok(())
+    }
+
     pub fn init(&mut self, new_mmio_start_addr: Option<usize>) -> Result<(), &'static str> {
         if let Some(addr) = new_mmio_start_addr {
             unsafe {
@@ -154,21 +268,14 @@ impl PL011UartInner {
         self.registers.cr.set(0);
         self.registers.icr.write(ICR::ALL::CLEAR);
 
-        self.registers.ibrd.write(IBRD::BAUD_DIVINT.val(26));
-        self.registers.fbrd.write(FBRD::BAUD_DIVFRAC.val(3));
-
         self.registers
-            .lcr_h
-            .write(LCR_H::FEN::FifosEnabled + LCR_H::WLEN::Eight);
-
-        self.registers.ifls.write(IFLS::RXIFLSEL::OneEigth);
+            .ifls
+            .write(IFLS::RXIFLSEL::OneEigth + IFLS::TXIFLSEL::OneEigth);
         self.registers
             .imsc
             .write(IMSC::RXIM::Enabled + IMSC::RTIM::Enabled);
 
-        self.registers
-            .cr
-            .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled);
+        self.apply_config(self.config)?;
 
         Ok(())
     }
@@ -185,33 +292,47 @@ impl PL011UartInner {
         }
     }
 
-    fn wait_for_rx_fifo(&self) {
-        while self.registers.fr.matches_all(FR::RXFE::SET) {
-            unsafe { nop() }
-        }
-    }
-
     fn write_char(&mut self, c: char) {
         self.wait_for_tx_fifo();
 
         self.registers.dr.set(c as u32)
     }
 
-    fn read_char(&mut self, block: bool) -> Option<char> {
-        if self.registers.fr.matches_all(FR::RXFE::SET) {
-            if !block {
-                return None;
-            }
+    fn tx_fifo_full(&self) -> bool {
+        self.registers.fr.matches_all(FR::TXFF::SET)
+    }
 
-            self.wait_for_rx_fifo()
+    /// Writes out everything buffered by spinning on TX FIFO
+    fn drain_tx(&mut self, tx: &mut RingBuffer<u8, TX_BUFFER_SIZE>) {
+        while let Some(byte) = tx.pop() {
+            self.write_char(byte as char);
         }
+        self.registers.imsc.modify(IMSC::TXIM::Disabled);
+    }
 
-        let res = self.registers.dr.get() as u8 as char;
+    /// Moves buffered bytes to TX FIFO while it has space. TX interrupt fires only when FIFO
+    /// level drops below the threshold, so it's enabled just while bytes remain buffered.
+    fn fill_tx_fifo(&mut self, tx: &mut RingBuffer<u8, TX_BUFFER_SIZE>) {
+        while !self.tx_fifo_full() {
+            match tx.pop() {
+                Some(byte) => self.registers.dr.set(byte as u32),
+                None => break,
+            }
+        }
 
-        if res == '\r' {
-            Some('\n')
+        if tx.is_empty() {
+            self.registers.imsc.modify(IMSC::TXIM::Disabled);
+        } else {
+            self.registers.imsc.modify(IMSC::TXIM::Enabled);
+        }
+    }
+
+    /// Takes a byte from RX FIFO if there is one
+    fn read_byte(&mut self) -> Option<u8> {
+        if self.registers.fr.matches_all(FR::RXFE::SET) {
+            None
         } else {
-            Some(res)
+            Some(self.registers.dr.get() as u8)
         }
     }
 }
@@ -235,13 +356,39 @@ impl fmt::Write for PL011UartInner {
 impl PL011Uart {
     const IRQ_NUMBER: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::UARTInt);
 
-    pub const unsafe fn new(mmio_descriptor: MMIODescriptor) -> Self {
+    /// `clock` is the UART reference clock in Hz
+    pub const unsafe fn new(mmio_descriptor: MMIODescriptor, clock: u32) -> Self {
         Self {
             mmio_descriptor,
             virt_mmio_start_addr: AtomicUsize::new(0),
-            inner: IRQSafeNullLock::new(PL011UartInner::new(mmio_descriptor.start_addr().addr())),
+            inner: IRQSpinLock::new(PL011UartInner::new(
+                mmio_descriptor.start_addr().addr(),
+                clock,
+                LineConfig::new(),
+            )),
+            rx_buffer: IRQSpinLock::new(RingBuffer::new()),
+            rx_waiters: WaitQueue::new(),
+            tx_buffer: IRQSpinLock::new(RingBuffer::new()),
+            tx_irq_enabled: AtomicBool::new(false),
         }
     }
+
+    /// Writes out everything buffered by spinning on TX FIFO, usable with IRQs masked
+    fn drain_tx(&self) {
+        self.inner
+            .map_locked(|inner| self.tx_buffer.map_locked(|tx| inner.drain_tx(tx)))
+    }
+
+    /// Line settings for the panic console, buffered output is written out first. Panicking
+    /// code may hold the UART locks, in that case the output is lost and settings default.
+    pub fn panic_line_config(&self) -> LineConfig {
+        self.inner
+            .try_map_locked(|inner| {
+                self.tx_buffer.try_map_locked(|tx| inner.drain_tx(tx));
+                inner.config
+            })
+            .unwrap_or_default()
+    }
 }
 
 impl Driver for PL011Uart {
@@ -261,6 +408,10 @@ impl Driver for PL011Uart {
         Ok(())
     }
 
+    fn device_nodes(&'static self) -> Vec<DeviceNode> {
+        vec![DeviceNode::new("console", Arc::new(Console))]
+    }
+
     fn register_irq_handler(&'static self) -> Result<(), &'static str> {
         statics::INTERRUPT_CONTROLLER.register_handler(
             Self::IRQ_NUMBER,
@@ -270,6 +421,7 @@ impl Driver for PL011Uart {
             },
         )?;
         statics::INTERRUPT_CONTROLLER.enable(Self::IRQ_NUMBER);
+        self.tx_irq_enabled.store(true, Ordering::Relaxed);
 
         Ok(())
     }
@@ -286,36 +438,94 @@ impl Driver for PL011Uart {
 }
 
 impl serial_console::Write for PL011Uart {
+    /// Queues `c` for the TX interrupt, spins only when TX buffer is full
     fn write_char(&self, c: char) {
-        self.inner.map_locked(|inner| inner.write_char(c))
+        if !self.tx_irq_enabled.load(Ordering::Relaxed) {
+            return self.inner.map_locked(|inner| inner.write_char(c));
+        }
+
+        self.inner.map_locked(|inner| {
+            self.tx_buffer.map_locked(|tx| {
+                if tx.is_full() {
+                    inner.wait_for_tx_fifo();
+                    inner.fill_tx_fifo(tx);
+                }
+                let _ = tx.push(c as u8);
+                inner.fill_tx_fifo(tx);
+            })
+        })
     }
 
     fn write_fmt(&self, args: Arguments) -> fmt::Result {
-        self.inner
-            .map_locked(|inner| fmt::Write::write_fmt(inner, args))
+        fmt::Write::write_fmt(&mut &*self, args)
     }
 
+    /// Blocks until TX buffer is empty and the last byte left the wire
     fn flush(&self) {
+        if self.tx_irq_enabled.load(Ordering::Relaxed) && !is_irq_masked() {
+            while !self.tx_buffer.map_locked(|tx| tx.is_empty()) {
+                unsafe { wfi() }
+            }
+        } else {
+            self.drain_tx();
+        }
+
         self.inner.map_locked(|inner| inner.flush())
     }
 }
 
+impl serial_console::Configure for PL011Uart {
+    fn line_config(&self) -> LineConfig {
+        self.inner.map_locked(|inner| inner.config)
+    }
+
+    /// Waits for pending output, so it isn't sent with the new settings.
+    /// Flow control moves CTS and RTS to GPIO 16 and 17.
+    fn set_line_config(&self, config: LineConfig) -> Result<(), &'static str> {
+        serial_console::Write::flush(self);
+
+        // CTS must not float while the UART honours it, so pins are switched to CTS/RTS before
+        // flow control is enabled and back only after it's disabled
+        let was_enabled = self.inner.map_locked(|inner| inner.config.flow_control);
+        if config.flow_control {
+            statics::GPIO_DRIVER.map_pl011_flow_control(true);
+        }
+
+        let res = self.inner.map_locked(|inner| inner.apply_config(config));
+        let enabled = if res.is_ok() {
+            config.flow_control
+        } else {
+            was_enabled
+        };
+        if !enabled {
+            statics::GPIO_DRIVER.map_pl011_flow_control(false);
+        }
+
+        res
+    }
+}
+
 impl serial_console::Read for PL011Uart {
+    /// Blocks until the IRQ handler buffers some input, bytes are returned as received
     fn read_char(&self) -> char {
-        self.inner
-            .map_locked(|inner| inner.read_char(true).expect("inner read_char"))
+        loop {
+            if let Some(c) = self.rx_buffer.map_locked(|rx| rx.pop()) {
+                return c as char;
+            }
+
+            self.rx_waiters
+                .wait_until(|| self.rx_buffer.map_locked(|rx| !rx.is_empty()));
+        }
     }
 
     fn clear(&self) {
-        while self
-            .inner
-            .map_locked(|inner| inner.read_char(false))
-            .is_some()
-        {}
+        self.inner
+            .map_locked(|inner| while inner.read_byte().is_some() {});
+        self.rx_buffer.map_locked(|rx| rx.clear());
     }
 }
 
-impl fmt::Write for PL011Uart {
+impl fmt::Write for &PL011Uart {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for c in s.chars() {
             serial_console::Write::write_char(self, c);
@@ -327,19 +537,27 @@ impl fmt::Write for PL011Uart {
 
 impl IRQHandler for PL011Uart {
     fn handle(&self) -> Result<(), &'static str> {
-        self.inner.map_locked(|inner| {
+        let rx_pending = self.inner.map_locked(|inner| {
             let pending = inner.registers.mis.extract();
+            let rx_pending = pending.matches_any(MIS::RXMIS::SET + MIS::RTMIS::SET);
 
-            inner.registers.icr.write(ICR::ALL::CLEAR);
+            if pending.matches_all(MIS::TXMIS::SET) {
+                self.tx_buffer.map_locked(|tx| inner.fill_tx_fifo(tx));
+            }
 
-            if pending.matches_any(MIS::RXMIS::SET + MIS::RTMIS::SET) {
-                while inner.read_char(false).is_some() {}
-                // while let Some(c) = inner.read_char(false) {
-                //    inner.write_char(c)
-                // }
+            // Draining RX FIFO deasserts RX interrupts, received bytes wait in the ring buffer
+            while let Some(byte) = inner.read_byte() {
+                self.rx_buffer.map_locked(|rx| rx.push(byte).ok());
             }
+            inner.registers.icr.write(ICR::ALL::CLEAR);
+
+            rx_pending
         });
 
+        if rx_pending {
+            self.rx_waiters.wake_all();
+        }
+
         Ok(())
     }
 }
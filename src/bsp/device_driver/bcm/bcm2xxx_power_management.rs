@@ -0,0 +1,109 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::ReadWrite,
+};
+
+use crate::{
+    arch::arch_impl::cpu::park,
+    bsp::device_driver::WrappedPointer,
+    common::{
+        driver::Driver,
+        memory::mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
+        sync::{IRQSpinLock, Mutex},
+    },
+};
+
+/// Every write to PM registers has to carry the password in its top byte
+const PM_PASSWORD: u32 = 0x5a00_0000;
+const PM_RSTC_WRCFG_MASK: u32 = 0x30;
+const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+/// Watchdog ticks run at ~65.5 kHz, few of them give the write time to complete
+const PM_WDOG_RESET_TICKS: u32 = 10;
+
+register_structs! {
+    pub RegisterBlock {
+        (0x00 => _reserved),
+        (0x1c => rstc: ReadWrite<u32>),
+        (0x20 => _rsts),
+        (0x24 => wdog: ReadWrite<u32>),
+        (0x28 => @END),
+    }
+}
+
+struct PowerManagementInner {
+    registers: WrappedPointer<RegisterBlock>,
+}
+
+/// Power management block, its watchdog is the only way to reset the board
+pub struct PowerManagement {
+    mmio_descriptor: MMIODescriptor,
+    virt_mmio_start_addr: AtomicUsize,
+    inner: IRQSpinLock<PowerManagementInner>,
+}
+
+impl PowerManagementInner {
+    const unsafe fn new(start: usize) -> Self {
+        Self {
+            registers: WrappedPointer::new(start),
+        }
+    }
+
+    fn reset(&mut self) {
+        let rstc = self.registers.rstc.get() & !PM_RSTC_WRCFG_MASK;
+
+        self.registers.wdog.set(PM_PASSWORD | PM_WDOG_RESET_TICKS);
+        self.registers
+            .rstc
+            .set(PM_PASSWORD | rstc | PM_RSTC_WRCFG_FULL_RESET);
+    }
+}
+
+impl PowerManagement {
+    pub const unsafe fn new(mmio_descriptor: MMIODescriptor) -> Self {
+        Self {
+            mmio_descriptor,
+            virt_mmio_start_addr: AtomicUsize::new(0),
+            inner: IRQSpinLock::new(PowerManagementInner::new(
+                mmio_descriptor.start_addr().addr(),
+            )),
+        }
+    }
+
+    /// Arms the watchdog for a full reset and waits for it to fire
+    pub fn reset(&self) -> ! {
+        self.inner.map_locked(|inner| inner.reset());
+
+        unsafe { park() }
+    }
+}
+
+impl Driver for PowerManagement {
+    fn compat(&self) -> &'static str {
+        "bcm power management"
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        let addr = map_kernel_mmio(self.compat(), self.mmio_descriptor)?;
+
+        self.inner.map_locked(|inner| {
+            inner.registers = WrappedPointer::new(addr.addr());
+        });
+        self.virt_mmio_start_addr
+            .store(addr.addr(), Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn virt_mmio_start_addr(&self) -> Option<usize> {
+        let addr = self.virt_mmio_start_addr.load(Ordering::Relaxed);
+
+        if addr == 0 {
+            None
+        } else {
+            Some(addr)
+        }
+    }
+}
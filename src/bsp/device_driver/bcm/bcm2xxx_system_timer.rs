@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use tock_registers::interfaces::{Readable, Writeable};
@@ -26,7 +27,7 @@ use crate::{
         exception::asynchronous::{IRQDescriptor, IRQHandler, IRQManager},
         memory::mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
         statics,
-        sync::{IRQSafeNullLock, Mutex},
+        sync::{IRQSpinLock, Mutex, ReadWriteLock, RwSpinLock},
         time::scheduling::{SchedulingManager, TickCallbackHandler},
     },
 };
@@ -60,15 +61,14 @@ struct SystemTimerInner {
 }
 
 struct Callbacks {
-    items: [Option<&'static (dyn TickCallbackHandler + Sync)>; 4],
-    callbacks_last: usize,
+    items: Vec<&'static (dyn TickCallbackHandler + Sync)>,
 }
 
 pub struct SystemTimer {
     descriptor: MMIODescriptor,
     virt_mmio_start_addr: AtomicUsize,
-    callbacks: IRQSafeNullLock<Callbacks>,
-    inner: IRQSafeNullLock<SystemTimerInner>,
+    callbacks: RwSpinLock<Callbacks>,
+    inner: IRQSpinLock<SystemTimerInner>,
 }
 
 impl SystemTimerInner {
@@ -101,17 +101,13 @@ impl SystemTimer {
     const IRQ_NUMBER: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::SystemTimer1);
 
     pub const unsafe fn new(descriptor: MMIODescriptor) -> Self {
-        const DEFAULT_CALLBACK: Option<&'static (dyn TickCallbackHandler + Sync)> = None;
         Self {
             descriptor,
             virt_mmio_start_addr: AtomicUsize::new(0),
 
-            callbacks: IRQSafeNullLock::new(Callbacks {
-                items: [DEFAULT_CALLBACK; 4],
-                callbacks_last: 0,
-            }),
+            callbacks: RwSpinLock::new(Callbacks { items: Vec::new() }),
 
-            inner: IRQSafeNullLock::new(SystemTimerInner::new(descriptor.start_addr().addr())),
+            inner: IRQSpinLock::new(SystemTimerInner::new(descriptor.start_addr().addr())),
         }
     }
 }
@@ -157,11 +153,14 @@ impl Driver for SystemTimer {
 impl IRQHandler for SystemTimer {
     fn handle(&self) -> Result<(), &'static str> {
         self.inner.map_locked(|inner| inner.handle_irq());
-        self.callbacks.map_locked(|callbacks| {
-            for callback in callbacks.items.iter().flatten() {
-                callback.handle()
-            }
-        });
+
+        // Scheduler callback may switch tasks, so callbacks can't run with the list locked
+        let nth_callback = |idx| self.callbacks.map_read(|c| c.items.get(idx).copied());
+        let mut idx = 0;
+        while let Some(callback) = nth_callback(idx) {
+            callback.handle();
+            idx += 1;
+        }
 
         Ok(())
     }
@@ -172,14 +171,9 @@ impl SchedulingManager for SystemTimer {
         &self,
         handler: &'static (dyn TickCallbackHandler + Sync),
     ) -> Result<(), &'static str> {
-        self.callbacks.map_locked(|callbacks| {
-            if callbacks.callbacks_last < 4 {
-                callbacks.items[callbacks.callbacks_last] = Some(handler);
-                callbacks.callbacks_last += 1;
-                Ok(())
-            } else {
-                Err("couldn't register handler")
-            }
-        })
+        self.callbacks
+            .map_write(|callbacks| callbacks.items.push(handler));
+
+        Ok(())
     }
 }
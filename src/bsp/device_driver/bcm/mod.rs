@@ -1,4 +1,5 @@
 pub mod bcm2xxx_gpio;
 pub mod bcm2xxx_interrupt_controller;
 pub mod bcm2xxx_pl011_uart;
+pub mod bcm2xxx_power_management;
 pub mod bcm2xxx_system_timer;
@@ -1 +1,29 @@
+use core::{arch::asm, ptr};
+
+use crate::common::memory::{
+    mmu::{descriptors::MMIODescriptor, map_kernel_mmio},
+    Address,
+    Physical,
+};
+
 pub const BOOT_CORE_ID: u64 = 0;
+pub const NUM_CORES: usize = 4;
+
+/// Firmware keeps secondary cores polling a 64 bit slot each, starting with core 0 at 0xd8.
+/// Once a slot becomes non zero its core jumps to the address in it, at EL2 with MMU off.
+const SPIN_TABLE: MMIODescriptor = MMIODescriptor::new(Address::new(0xd8), NUM_CORES * 8);
+
+/// Releases secondary cores from the firmware spin table into physical address `entry`
+pub unsafe fn start_secondary_cores(entry: Address<Physical>) -> Result<(), &'static str> {
+    let spin_table = map_kernel_mmio("spin table", SPIN_TABLE)?;
+
+    for core_id in (0..NUM_CORES).filter(|&core_id| core_id as u64 != BOOT_CORE_ID) {
+        let slot = (spin_table.addr() + core_id * 8) as *mut u64;
+        ptr::write_volatile(slot, entry.addr() as u64);
+    }
+
+    // Cores sleep in `wfe` between polls
+    asm!("dsb sy", "sev", options(nostack));
+
+    Ok(())
+}
@@ -1,5 +1,8 @@
 use crate::{
-    common::driver::{Driver, DriverManager},
+    common::{
+        driver::{Driver, DriverManager},
+        fs::devfs,
+    },
     info,
 };
 
@@ -43,6 +46,16 @@ impl<const T: usize, const L: usize> DriverManager for BSPDriverManager<T, L> {
 
         Ok(())
     }
+
+    fn publish_device_nodes(&'static self) -> Result<(), &'static str> {
+        for driver in self.early_drivers.iter().chain(self.late_drivers.iter()) {
+            for node in driver.device_nodes() {
+                devfs::register(node, driver.compat())?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl<const T: usize, const L: usize> BSPDriverManager<T, L> {
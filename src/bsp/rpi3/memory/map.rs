@@ -14,6 +14,9 @@ pub mod mmio {
     pub const UART_START: Address<Physical> = Address::new(0x3F20_1000);
     pub const UART_SIZE: usize = 0x48;
 
+    pub const PM_START: Address<Physical> = Address::new(0x3F10_0000);
+    pub const PM_SIZE: usize = 0x28;
+
     pub const LOCAL_IC_START: Address<Physical> = Address::new(0x4000_0000);
     pub const LOCAL_IC_SIZE: usize = 0x100;
 
@@ -36,3 +39,7 @@ pub mod user {
     pub const PAGING_MEMORY_SIZE: usize = HIGH_MEMORY.addr() - LOW_MEMORY.addr();
     pub const PAGE_COUNT: usize = PAGING_MEMORY_SIZE / Granule64KB::SIZE;
 }
+
+pub mod heap {
+    pub const KERNEL_HEAP_SIZE: usize = 4 * 1024 * 1024;
+}
@@ -1,11 +1,17 @@
 use crate::{
-    bsp::device::memory::{
-        boot_core_stack_size,
-        boot_core_stack_start,
-        rw_size,
-        rw_start,
-        rx_size,
-        rx_start,
+    bsp::device::{
+        cpu::{BOOT_CORE_ID, NUM_CORES},
+        memory::{
+            boot_core_stack_size,
+            boot_core_stack_start,
+            core_stack_start,
+            map::user::{LOW_MEMORY, PAGE_COUNT},
+            rw_size,
+            rw_start,
+            rx_size,
+            rx_start,
+            SECONDARY_CORE_STACK_SIZE,
+        },
     },
     common::memory::{
         mmu::{
@@ -27,6 +33,11 @@ use crate::{
 
 pub type KernelGranule = TranslationGranule<{ 64 * 1024 }>;
 pub type KernelAddrSpace = AddressSpace<{ 8 * 1024 * 1024 * 1024 }>;
+pub type UserAddrSpace = AddressSpace<{ 1024 * 1024 * 1024 }>;
+
+/// Kernel is linked into the topmost `KernelAddrSpace`, which is translated through TTBR1.
+/// Has to match `__kernel_virt_offset` in `link.ld`.
+pub const KERNEL_VIRT_OFFSET: usize = !(KernelAddrSpace::SIZE - 1);
 
 const fn size_to_num_pages(size: usize) -> usize {
     assert!(size > 0);
@@ -62,6 +73,26 @@ fn boot_core_stack_ppage_desc() -> PageSliceDescriptor<Physical> {
     boot_core_stack_vpage_desc().into()
 }
 
+/// Guard pages in between stay unmapped
+fn secondary_core_stack_vpage_desc(core_id: u64) -> PageSliceDescriptor<Virtual> {
+    PageSliceDescriptor::from_addr(
+        core_stack_start(core_id),
+        size_to_num_pages(SECONDARY_CORE_STACK_SIZE),
+    )
+}
+
+fn secondary_core_stack_ppage_desc(core_id: u64) -> PageSliceDescriptor<Physical> {
+    secondary_core_stack_vpage_desc(core_id).into()
+}
+
+fn physical_memory_ppage_desc() -> PageSliceDescriptor<Physical> {
+    PageSliceDescriptor::from_addr(LOW_MEMORY, PAGE_COUNT)
+}
+
+fn physical_memory_vpage_desc() -> PageSliceDescriptor<Virtual> {
+    physical_memory_ppage_desc().into()
+}
+
 pub fn map_kernel_binary() -> Result<(), &'static str> {
     map_kernel_pages_at(
         "kernel code + RO data",
@@ -99,5 +130,31 @@ pub fn map_kernel_binary() -> Result<(), &'static str> {
     )
     .expect("map Kernel BOOT-CORE stack");
 
+    for core_id in (0..NUM_CORES as u64).filter(|&core_id| core_id != BOOT_CORE_ID) {
+        map_kernel_pages_at(
+            "kernel secondary core stack",
+            secondary_core_stack_vpage_desc(core_id),
+            secondary_core_stack_ppage_desc(core_id),
+            Attributes {
+                memory: MemoryAttributes::CacheableDRAM,
+                access: AccessPermissions::RW,
+                execute: Execute::Never,
+            },
+        )
+        .expect("map Kernel secondary core stack");
+    }
+
+    map_kernel_pages_at(
+        "physical memory",
+        physical_memory_vpage_desc(),
+        physical_memory_ppage_desc(),
+        Attributes {
+            memory: MemoryAttributes::CacheableDRAM,
+            access: AccessPermissions::RW,
+            execute: Execute::Never,
+        },
+    )
+    .expect("map physical memory");
+
     Ok(())
 }
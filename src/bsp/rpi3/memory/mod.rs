@@ -1,6 +1,9 @@
 use core::{cell::UnsafeCell, ops::Range};
 
-use crate::common::memory::{Address, Virtual};
+use crate::{
+    bsp::device::cpu::BOOT_CORE_ID,
+    common::memory::{Address, Virtual},
+};
 
 pub mod map;
 pub mod mmu;
@@ -15,10 +18,24 @@ extern "Rust" {
     static __rw_start: UnsafeCell<()>;
     static __rw_ende: UnsafeCell<()>;
 
+    static __initramfs_start: UnsafeCell<()>;
+    static __initramfs_ende: UnsafeCell<()>;
+
+    static __kernel_symbols_start: UnsafeCell<()>;
+    static __kernel_symbols_ende: UnsafeCell<()>;
+
     static __boot_core_stack_start: UnsafeCell<()>;
     static __boot_core_stack_ende: UnsafeCell<()>;
+
+    static __secondary_core_stacks_start: UnsafeCell<()>;
 }
 
+/// Has to match the secondary core stacks layout in `link.ld`
+const SECONDARY_CORE_STACK_GUARD_SIZE: usize = 64 * 1024;
+pub const SECONDARY_CORE_STACK_SIZE: usize = 128 * 1024;
+pub const SECONDARY_CORE_STACK_SLOT_SIZE: usize =
+    SECONDARY_CORE_STACK_GUARD_SIZE + SECONDARY_CORE_STACK_SIZE;
+
 fn bss_start() -> usize {
     unsafe { __bss_start.get() as usize }
 }
@@ -47,6 +64,22 @@ pub fn rw_size() -> usize {
     unsafe { (__rw_ende.get() as usize) - (__rw_start.get() as usize) }
 }
 
+pub fn initramfs() -> &'static [u8] {
+    unsafe {
+        let start = __initramfs_start.get() as usize;
+        let size = (__initramfs_ende.get() as usize) - start;
+        core::slice::from_raw_parts(start as *const u8, size)
+    }
+}
+
+pub fn kernel_symbols() -> &'static [u8] {
+    unsafe {
+        let start = __kernel_symbols_start.get() as usize;
+        let size = (__kernel_symbols_ende.get() as usize) - start;
+        core::slice::from_raw_parts(start as *const u8, size)
+    }
+}
+
 pub fn boot_core_stack_start() -> Address<Virtual> {
     Address::new(unsafe { __boot_core_stack_start.get() as usize })
 }
@@ -58,3 +91,26 @@ pub fn boot_core_stack_ende() -> Address<Virtual> {
 pub fn boot_core_stack_size() -> usize {
     unsafe { (__boot_core_stack_ende.get() as usize) - (__boot_core_stack_start.get() as usize) }
 }
+
+/// Lowest address of the stack of `core_id`
+pub fn core_stack_start(core_id: u64) -> Address<Virtual> {
+    if core_id == BOOT_CORE_ID {
+        return boot_core_stack_start();
+    }
+
+    let stacks = unsafe { __secondary_core_stacks_start.get() as usize };
+
+    Address::new(
+        stacks
+            + (core_id as usize - 1) * SECONDARY_CORE_STACK_SLOT_SIZE
+            + SECONDARY_CORE_STACK_GUARD_SIZE,
+    )
+}
+
+pub fn core_stack_ende(core_id: u64) -> Address<Virtual> {
+    if core_id == BOOT_CORE_ID {
+        return boot_core_stack_ende();
+    }
+
+    core_stack_start(core_id) + SECONDARY_CORE_STACK_SIZE
+}
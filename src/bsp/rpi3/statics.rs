@@ -1,4 +1,4 @@
-use core::fmt;
+use core::{fmt, sync::atomic::AtomicUsize};
 
 use crate::bsp::{
     device_driver::bcm::{bcm2xxx_gpio::Gpio, bcm2xxx_pl011_uart::PL011Uart},
@@ -7,8 +7,12 @@ use crate::bsp::{
 
 pub static GPIO_DRIVER: Gpio =
     unsafe { Gpio::new(MMIODescriptor::new(mmio::GPIO_START, mmio::GPIO_SIZE)) };
-pub static UART_DRIVER: PL011Uart =
-    unsafe { PL011Uart::new(MMIODescriptor::new(mmio::UART_START, mmio::UART_SIZE)) };
+pub static UART_DRIVER: PL011Uart = unsafe {
+    PL011Uart::new(
+        MMIODescriptor::new(mmio::UART_START, mmio::UART_SIZE),
+        UART_CLOCK_HZ,
+    )
+};
 pub static INTERRUPT_CONTROLLER: InterruptController = unsafe {
     InterruptController::new(
         MMIODescriptor::new(mmio::LOCAL_IC_START, mmio::LOCAL_IC_SIZE),
@@ -17,10 +21,16 @@ pub static INTERRUPT_CONTROLLER: InterruptController = unsafe {
 };
 pub static SYSTEM_TIMER_DRIVER: SystemTimer =
     unsafe { SystemTimer::new(MMIODescriptor::new(mmio::TIMER_START, mmio::TIMER_SIZE)) };
+pub static POWER_MANAGEMENT: PowerManagement =
+    unsafe { PowerManagement::new(MMIODescriptor::new(mmio::PM_START, mmio::PM_SIZE)) };
 
-pub static BSP_DRIVER_MANAGER: BSPDriverManager<2, 2> = BSPDriverManager {
+pub static BSP_DRIVER_MANAGER: BSPDriverManager<2, 3> = BSPDriverManager {
     early_drivers: [&GPIO_DRIVER, &UART_DRIVER],
-    late_drivers: [&INTERRUPT_CONTROLLER, &SYSTEM_TIMER_DRIVER],
+    late_drivers: [
+        &INTERRUPT_CONTROLLER,
+        &SYSTEM_TIMER_DRIVER,
+        &POWER_MANAGEMENT,
+    ],
 };
 
 pub use self::UART_DRIVER as CONSOLE;
@@ -30,16 +40,26 @@ use crate::{
         bcm2xxx_gpio::GpioInner,
         bcm2xxx_interrupt_controller::InterruptController,
         bcm2xxx_pl011_uart::PL011UartInner,
+        bcm2xxx_power_management::PowerManagement,
         bcm2xxx_system_timer::SystemTimer,
     },
     common::{driver::Driver, memory::mmu::descriptors::MMIODescriptor},
 };
 
-pub static mut LOG_LEVEL: usize = 2;
+/// Level used by modules without their own filter, see `log::set_log_level`
+pub static LOG_LEVEL: AtomicUsize = AtomicUsize::new(2);
+
+/// PL011 reference clock set by the firmware, `init_uart_clock` in config.txt changes it
+pub const UART_CLOCK_HZ: u32 = 48_000_000;
 
+/// Synchronous console, output buffered for the TX interrupt is written out first
 pub unsafe fn panic_console() -> impl fmt::Write {
     let mut gpio = GpioInner::new(mmio::GPIO_START.addr());
-    let mut uart = PL011UartInner::new(mmio::UART_START.addr());
+    let mut uart = PL011UartInner::new(
+        mmio::UART_START.addr(),
+        UART_CLOCK_HZ,
+        UART_DRIVER.panic_line_config(),
+    );
 
     let gpio_addr = GPIO_DRIVER.virt_mmio_start_addr();
     let uart_addr = UART_DRIVER.virt_mmio_start_addr();
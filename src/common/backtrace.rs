@@ -0,0 +1,55 @@
+use core::fmt;
+
+use crate::{
+    arch::arch_impl::cpu::{
+        backtrace::{frame_pointer, FrameWalker},
+        registers::mpidr_el1::core_id_el1,
+    },
+    bsp::device::memory::{core_stack_ende, core_stack_start},
+    common::{scheduler::SCHEDULER, symbols::Symbolized},
+};
+
+/// Call chain of a function given its frame pointer and, for interrupted code, its pc
+pub struct Backtrace {
+    pc: Option<usize>,
+    fp: usize,
+}
+
+impl Backtrace {
+    /// Starts at the function calling `here`
+    #[inline(always)]
+    pub fn here() -> Self {
+        Self {
+            pc: None,
+            fp: frame_pointer(),
+        }
+    }
+
+    pub fn new(pc: usize, fp: usize) -> Self {
+        Self { pc: Some(pc), fp }
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let core_id = unsafe { core_id_el1() };
+        let stacks = [
+            core_stack_start(core_id).addr()..core_stack_ende(core_id).addr(),
+            SCHEDULER.current_stack().unwrap_or(0..0),
+        ];
+
+        writeln!(f, "BACKTRACE:")?;
+        let mut frame = 0;
+        if let Some(pc) = self.pc {
+            writeln!(f, "  #{:<2} {}", frame, Symbolized(pc))?;
+            frame += 1;
+        }
+        for lr in FrameWalker::new(self.fp, &stacks) {
+            // Return address points past the call, report the call itself
+            writeln!(f, "  #{:<2} {}", frame, Symbolized(lr.saturating_sub(4)))?;
+            frame += 1;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use core::fmt;
+
+use crate::common::{
+    ring_buffer::RingBuffer,
+    sync::{IRQSpinLock, Mutex},
+};
+
+const DMESG_SIZE: usize = 16 * 1024;
+
+/// Formatted log records, oldest ones get overwritten once the buffer fills up
+static DMESG: IRQSpinLock<RingBuffer<u8, DMESG_SIZE>> = IRQSpinLock::new(RingBuffer::new());
+
+struct RecordWriter<'a>(&'a mut RingBuffer<u8, DMESG_SIZE>);
+
+impl fmt::Write for RecordWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.0.push_overwrite(byte);
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends `args` as a single record, it should end with a newline
+pub fn record(args: fmt::Arguments) {
+    DMESG.map_locked(|dmesg| {
+        let _ = fmt::Write::write_fmt(&mut RecordWriter(dmesg), args);
+    })
+}
+
+pub fn len() -> usize {
+    DMESG.map_locked(|dmesg| dmesg.len())
+}
+
+/// Copies buffered log starting at `offset` bytes from the oldest kept record
+pub fn read_at(offset: usize, buf: &mut [u8]) -> usize {
+    DMESG.map_locked(|dmesg| {
+        let mut read = 0;
+        for (dst, byte) in buf.iter_mut().zip(dmesg.iter().skip(offset)) {
+            *dst = byte;
+            read += 1;
+        }
+
+        read
+    })
+}
+
+/// Writes out whole records from the last `bytes` of the log. Meant for panics, so it fails
+/// instead of waiting when the log is locked.
+pub fn dump_tail(out: &mut impl fmt::Write, bytes: usize) -> fmt::Result {
+    DMESG
+        .try_map_locked(|dmesg| {
+            let skip = dmesg.len().saturating_sub(bytes);
+            let mut tail = dmesg.iter().skip(skip);
+
+            // First record is most likely cut in half
+            if skip > 0 {
+                tail.by_ref().find(|&byte| byte == b'\n');
+            }
+
+            tail.try_for_each(|byte| out.write_char(byte as char))
+        })
+        .unwrap_or(Err(fmt::Error))
+}
@@ -1,3 +1,7 @@
+use alloc::vec::Vec;
+
+use crate::common::fs::devfs::DeviceNode;
+
 pub trait Driver {
     fn compat(&self) -> &'static str;
     unsafe fn init(&self) -> Result<(), &'static str> {
@@ -12,6 +16,10 @@ pub trait Driver {
     fn virt_mmio_start_addr(&self) -> Option<usize> {
         None
     }
+    /// Device files to publish in devfs, they are listed under `compat`
+    fn device_nodes(&'static self) -> Vec<DeviceNode> {
+        Vec::new()
+    }
 }
 
 pub trait DriverManager {
@@ -19,4 +27,5 @@ pub trait DriverManager {
     unsafe fn post_early_drivers(&self) -> Result<(), &'static str>;
     unsafe fn init_late_drivers(&self) -> Result<(), &'static str>;
     fn register_irq_handlers(&'static self) -> Result<(), &'static str>;
+    fn publish_device_nodes(&'static self) -> Result<(), &'static str>;
 }
@@ -0,0 +1,150 @@
+use alloc::vec::Vec;
+use core::{mem::size_of, slice};
+
+use super::{Elf, ProgramHeader};
+use crate::{
+    bsp::device::memory::mmu::{KernelGranule, UserAddrSpace},
+    common::{
+        fs::file_table::FileTable,
+        memory::{user_space::UserAddressSpace, Address, Virtual},
+        scheduler::spawn_user_process,
+    },
+};
+
+/// Stack grows down from the end of user address space
+const USER_STACK_TOP: usize = UserAddrSpace::SIZE;
+const USER_STACK_PAGES: usize = 16;
+
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+
+/// Loads executable into a fresh address space and starts it as a new user process with stdio
+/// bound to the console. Segments must not share pages, so programs have to be linked with
+/// `-z max-page-size=0x10000`.
+pub fn spawn_elf(image: &[u8], argv: &[&str], envp: &[&str]) -> Result<u64, &'static str> {
+    let elf = Elf::parse(image)?;
+    let mut mm = UserAddressSpace::new()?;
+
+    for ph in elf.program_headers().filter(ProgramHeader::is_load) {
+        load_segment(&elf, &ph, &mut mm)?;
+    }
+
+    if !mm.contains(elf.entry() as usize, 4, false, true) {
+        return Err("elf: entry point is not executable");
+    }
+
+    let stack = setup_stack(&elf, &mut mm, argv, envp)?;
+
+    unsafe {
+        spawn_user_process(
+            Address::new(elf.entry() as usize),
+            stack,
+            mm,
+            FileTable::with_console(),
+        )
+    }
+}
+
+/// Copies file backed part of the segment, pages are zeroed when mapped, which covers BSS
+fn load_segment(
+    elf: &Elf,
+    ph: &ProgramHeader,
+    mm: &mut UserAddressSpace,
+) -> Result<(), &'static str> {
+    if ph.is_writable() && ph.is_executable() {
+        return Err("elf: segment is both writable and executable");
+    }
+    if ph.memsz == 0 {
+        return Ok(());
+    }
+
+    let vaddr = ph.vaddr as usize;
+    let start = Address::<Virtual>::new(vaddr).align_down::<{ KernelGranule::SHIFT }>();
+    let end = vaddr + ph.memsz as usize;
+    let num_pages = (end - start.addr() + KernelGranule::MASK) >> KernelGranule::SHIFT;
+
+    mm.reserve_region(start, num_pages, ph.is_writable(), ph.is_executable())?;
+    mm.copy_to_user(Address::new(vaddr), elf.segment_data(ph))
+}
+
+fn auxiliary_vector(elf: &Elf) -> Vec<(u64, u64)> {
+    let header = elf.header();
+    let mut auxv = Vec::new();
+
+    // Program headers are visible to the program only if some segment loads them
+    let phdr = elf.program_headers().find(|ph| {
+        ph.is_load() && ph.offset <= header.phoff && header.phoff < ph.offset + ph.filesz
+    });
+    if let Some(ph) = phdr {
+        auxv.push((AT_PHDR, ph.vaddr + (header.phoff - ph.offset)));
+    }
+
+    auxv.push((AT_PHENT, size_of::<ProgramHeader>() as u64));
+    auxv.push((AT_PHNUM, header.phnum as u64));
+    auxv.push((AT_PAGESZ, KernelGranule::SIZE as u64));
+    auxv.push((AT_ENTRY, elf.entry()));
+
+    auxv
+}
+
+/// Lays out the initial stack the way SysV ABI expects it: `argc`, `argv` and `envp` arrays
+/// terminated with null, auxiliary vector and strings they point to at the very top
+fn setup_stack(
+    elf: &Elf,
+    mm: &mut UserAddressSpace,
+    argv: &[&str],
+    envp: &[&str],
+) -> Result<Address<Virtual>, &'static str> {
+    let stack_bottom = USER_STACK_TOP - (USER_STACK_PAGES << KernelGranule::SHIFT);
+    mm.reserve_region(Address::new(stack_bottom), USER_STACK_PAGES, true, false)?;
+
+    let mut strings = Vec::new();
+    let mut offsets = Vec::with_capacity(argv.len() + envp.len());
+    for s in argv.iter().chain(envp) {
+        offsets.push(strings.len());
+        strings.extend_from_slice(s.as_bytes());
+        strings.push(0);
+    }
+
+    let strings_start = USER_STACK_TOP
+        .checked_sub(strings.len())
+        .map(|start| start & !0xf)
+        .filter(|start| *start >= stack_bottom)
+        .ok_or("elf: arguments don't fit on the stack")?;
+    let pointers: Vec<u64> = offsets
+        .iter()
+        .map(|offset| (strings_start + offset) as u64)
+        .collect();
+    let (argv_pointers, envp_pointers) = pointers.split_at(argv.len());
+
+    let mut words = Vec::new();
+    words.push(argv.len() as u64);
+    words.extend_from_slice(argv_pointers);
+    words.push(0);
+    words.extend_from_slice(envp_pointers);
+    words.push(0);
+    for (key, value) in auxiliary_vector(elf) {
+        words.push(key);
+        words.push(value);
+    }
+    words.push(AT_NULL);
+    words.push(0);
+
+    let words_size = words.len() * size_of::<u64>();
+    // Aligned before the bound check, aligning moves it further down
+    let sp = strings_start
+        .checked_sub(words_size)
+        .map(|sp| sp & !0xf)
+        .filter(|sp| *sp >= stack_bottom)
+        .ok_or("elf: arguments don't fit on the stack")?;
+
+    let words = unsafe { slice::from_raw_parts(words.as_ptr() as *const u8, words_size) };
+    mm.copy_to_user(Address::new(strings_start), &strings)?;
+    mm.copy_to_user(Address::new(sp), words)?;
+
+    Ok(Address::new(sp))
+}
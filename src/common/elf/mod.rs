@@ -0,0 +1,156 @@
+use core::{convert::TryFrom, mem::size_of, ptr};
+
+pub mod loader;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_VERSION_CURRENT: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_AARCH64: u16 = 183;
+
+pub const PT_LOAD: u32 = 1;
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct ElfHeader {
+    pub ident: [u8; 16],
+    pub kind: u16,
+    pub machine: u16,
+    pub version: u32,
+    pub entry: u64,
+    pub phoff: u64,
+    pub shoff: u64,
+    pub flags: u32,
+    pub ehsize: u16,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    pub shnum: u16,
+    pub shstrndx: u16,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct ProgramHeader {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+    pub align: u64,
+}
+
+/// Validated ELF64 executable for aarch64, borrowed from the image it was parsed from
+pub struct Elf<'a> {
+    image: &'a [u8],
+    header: ElfHeader,
+}
+
+/// Reads `T` at `offset`, image bytes don't have to be aligned
+fn read_struct<T: Copy>(image: &[u8], offset: u64) -> Result<T, &'static str> {
+    let offset = usize::try_from(offset).map_err(|_| "elf: offset out of range")?;
+    let end = offset
+        .checked_add(size_of::<T>())
+        .ok_or("elf: offset out of range")?;
+    if end > image.len() {
+        return Err("elf: structure exceeds image");
+    }
+
+    Ok(unsafe { ptr::read_unaligned(image[offset..end].as_ptr() as *const T) })
+}
+
+impl ProgramHeader {
+    pub fn is_load(&self) -> bool {
+        self.kind == PT_LOAD
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.flags & PF_W != 0
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.flags & PF_X != 0
+    }
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(image: &'a [u8]) -> Result<Self, &'static str> {
+        let header: ElfHeader = read_struct(image, 0)?;
+
+        if header.ident[0..4] != ELF_MAGIC {
+            return Err("elf: bad magic");
+        }
+        if header.ident[4] != ELF_CLASS_64 {
+            return Err("elf: not a 64 bit image");
+        }
+        if header.ident[5] != ELF_DATA_LSB {
+            return Err("elf: not a little endian image");
+        }
+        if header.ident[6] != ELF_VERSION_CURRENT || header.version != ELF_VERSION_CURRENT as u32 {
+            return Err("elf: unsupported version");
+        }
+        if header.kind != ET_EXEC {
+            return Err("elf: not an executable");
+        }
+        if header.machine != EM_AARCH64 {
+            return Err("elf: not an aarch64 image");
+        }
+        if header.phentsize as usize != size_of::<ProgramHeader>() {
+            return Err("elf: unexpected program header size");
+        }
+
+        let elf = Self { image, header };
+        for idx in 0..header.phnum {
+            let ph = elf.program_header(idx)?;
+            if ph.is_load() {
+                elf.check_segment(&ph)?;
+            }
+        }
+
+        Ok(elf)
+    }
+
+    fn check_segment(&self, ph: &ProgramHeader) -> Result<(), &'static str> {
+        if ph.filesz > ph.memsz {
+            return Err("elf: segment file size exceeds memory size");
+        }
+        if ph.vaddr.checked_add(ph.memsz).is_none() {
+            return Err("elf: segment wraps around address space");
+        }
+        match ph.offset.checked_add(ph.filesz) {
+            Some(end) if end <= self.image.len() as u64 => Ok(()),
+            _ => Err("elf: segment exceeds image"),
+        }
+    }
+
+    fn program_header(&self, idx: u16) -> Result<ProgramHeader, &'static str> {
+        let offset = (idx as u64)
+            .checked_mul(size_of::<ProgramHeader>() as u64)
+            .and_then(|offset| offset.checked_add(self.header.phoff))
+            .ok_or("elf: offset out of range")?;
+
+        read_struct(self.image, offset)
+    }
+
+    pub fn header(&self) -> &ElfHeader {
+        &self.header
+    }
+
+    pub fn entry(&self) -> u64 {
+        self.header.entry
+    }
+
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        (0..self.header.phnum).filter_map(move |idx| self.program_header(idx).ok())
+    }
+
+    /// Bytes of the segment stored in the image, validated by `parse`
+    pub fn segment_data(&self, ph: &ProgramHeader) -> &'a [u8] {
+        &self.image[ph.offset as usize..(ph.offset + ph.filesz) as usize]
+    }
+}
@@ -16,6 +16,15 @@ pub trait IRQManager {
     fn handle_pending<'ctx>(&'ctx self, token: IRQContext<'ctx>);
 }
 
+/// Inter-processor interrupts, cores use them to make each other reschedule
+pub trait IPIManager {
+    fn register_ipi_handler(&self, descriptor: IRQDescriptor) -> Result<(), &'static str>;
+    /// Lets the calling core receive IPIs
+    fn enable_ipi(&self);
+    fn send_ipi(&self, core_id: usize);
+}
+
+#[derive(Copy, Clone)]
 pub struct IRQContext<'ctx> {
     _phantom: PhantomData<&'ctx ()>,
 }
@@ -0,0 +1,22 @@
+use crate::common::{
+    fs::{Inode, InodeKind},
+    syscall::Errno,
+    tty::TTY,
+};
+
+/// Character device of the serial console, input goes through the tty line discipline
+pub struct Console;
+
+impl Inode for Console {
+    fn kind(&self) -> InodeKind {
+        InodeKind::CharDevice
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(TTY.read(buf))
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, Errno> {
+        Ok(TTY.write(buf))
+    }
+}
@@ -0,0 +1,248 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{
+    common::{
+        dmesg,
+        fs::{FileSystem, Inode, InodeKind},
+        statics::CLOCK_TIMER,
+        sync::{IRQSpinLock, Mutex},
+        syscall::Errno,
+        time::clock::ClockManager,
+    },
+    info,
+};
+
+/// Compat string of nodes provided by the kernel itself rather than a driver
+const KERNEL_COMPAT: &str = "kernel";
+/// Read only listing of nodes together with compat strings of their drivers
+const DRIVERS_NODE: &str = "drivers";
+
+static NODES: IRQSpinLock<Vec<RegisteredNode>> = IRQSpinLock::new(Vec::new());
+static RANDOM_STATE: IRQSpinLock<u64> = IRQSpinLock::new(0);
+
+/// Device file published by a driver through `Driver::device_nodes`
+pub struct DeviceNode {
+    pub name: String,
+    pub inode: Arc<dyn Inode>,
+}
+
+struct RegisteredNode {
+    node: DeviceNode,
+    compat: &'static str,
+}
+
+/// Filesystem of device files, usually mounted at `/dev`. Nodes are global, so every mount
+/// shows the same devices.
+pub struct DevFs;
+
+struct DevFsRoot;
+
+struct DriversListing;
+
+/// Discards writes, reads return end of file
+pub struct Null;
+
+/// Discards writes, reads return zeroes
+pub struct Zero;
+
+/// Kernel log kept in memory, read only
+pub struct Kmsg;
+
+/// xorshift64* generator seeded with uptime, not suitable for cryptography
+pub struct Random;
+
+impl DeviceNode {
+    pub fn new(name: &str, inode: Arc<dyn Inode>) -> Self {
+        Self {
+            name: name.to_string(),
+            inode,
+        }
+    }
+}
+
+/// Makes `node` visible under devfs root, `compat` identifies the driver backing it
+pub fn register(node: DeviceNode, compat: &'static str) -> Result<(), &'static str> {
+    if node.name == DRIVERS_NODE || node.name.contains('/') {
+        return Err("invalid device node name");
+    }
+
+    NODES.map_locked(|nodes| {
+        if nodes
+            .iter()
+            .any(|registered| registered.node.name == node.name)
+        {
+            return Err("device node already registered");
+        }
+
+        nodes.push(RegisteredNode { node, compat });
+        Ok(())
+    })
+}
+
+/// Registers devices provided by the kernel
+pub fn init() -> Result<(), &'static str> {
+    register(DeviceNode::new("null", Arc::new(Null)), KERNEL_COMPAT)?;
+    register(DeviceNode::new("zero", Arc::new(Zero)), KERNEL_COMPAT)?;
+    register(DeviceNode::new("random", Arc::new(Random)), KERNEL_COMPAT)?;
+    register(DeviceNode::new("kmsg", Arc::new(Kmsg)), KERNEL_COMPAT)
+}
+
+pub fn print_status() {
+    info!("device nodes:");
+    NODES.map_locked(|nodes| {
+        for registered in nodes.iter() {
+            info!("  - {}: `{}`", registered.node.name, registered.compat);
+        }
+    })
+}
+
+impl FileSystem for DevFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(DevFsRoot)
+    }
+}
+
+impl Inode for DevFsRoot {
+    fn kind(&self) -> InodeKind {
+        InodeKind::Directory
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, Errno> {
+        if name == DRIVERS_NODE {
+            return Ok(Arc::new(DriversListing));
+        }
+
+        NODES.map_locked(|nodes| {
+            nodes
+                .iter()
+                .find(|registered| registered.node.name == name)
+                .map(|registered| registered.node.inode.clone())
+                .ok_or(Errno::ENOENT)
+        })
+    }
+
+    fn list(&self) -> Result<Vec<String>, Errno> {
+        let mut names: Vec<String> = NODES.map_locked(|nodes| {
+            nodes
+                .iter()
+                .map(|registered| registered.node.name.clone())
+                .collect()
+        });
+        names.push(DRIVERS_NODE.to_string());
+
+        Ok(names)
+    }
+}
+
+impl DriversListing {
+    fn contents() -> String {
+        NODES.map_locked(|nodes| {
+            nodes
+                .iter()
+                .map(|registered| format!("{}\t{}\n", registered.node.name, registered.compat))
+                .collect()
+        })
+    }
+}
+
+impl Inode for DriversListing {
+    fn kind(&self) -> InodeKind {
+        InodeKind::File
+    }
+
+    fn size(&self) -> usize {
+        Self::contents().len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        let contents = Self::contents();
+        let remaining = contents.as_bytes().get(offset..).unwrap_or(&[]);
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+
+        Ok(len)
+    }
+}
+
+impl Inode for Null {
+    fn kind(&self) -> InodeKind {
+        InodeKind::CharDevice
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(0)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, Errno> {
+        Ok(buf.len())
+    }
+}
+
+impl Inode for Zero {
+    fn kind(&self) -> InodeKind {
+        InodeKind::CharDevice
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        buf.fill(0);
+
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, Errno> {
+        Ok(buf.len())
+    }
+}
+
+impl Inode for Kmsg {
+    fn kind(&self) -> InodeKind {
+        InodeKind::File
+    }
+
+    fn size(&self) -> usize {
+        dmesg::len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(dmesg::read_at(offset, buf))
+    }
+}
+
+impl Random {
+    fn next(state: &mut u64) -> u64 {
+        if *state == 0 {
+            *state = CLOCK_TIMER.map_locked(|t| t.uptime()).as_nanos() as u64 | 1;
+        }
+
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Inode for Random {
+    fn kind(&self) -> InodeKind {
+        InodeKind::CharDevice
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        RANDOM_STATE.map_locked(|state| {
+            for chunk in buf.chunks_mut(8) {
+                let bytes = Self::next(state).to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        });
+
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, Errno> {
+        Ok(buf.len())
+    }
+}
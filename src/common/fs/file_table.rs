@@ -0,0 +1,90 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::common::{
+    fs::{console::Console, AccessMode, File, InodeFile},
+    syscall::Errno,
+};
+
+pub const MAX_FILES: usize = 16;
+
+pub const STDIN: usize = 0;
+pub const STDOUT: usize = 1;
+pub const STDERR: usize = 2;
+
+const NO_FILE: Option<Arc<dyn File>> = None;
+
+/// Open files of a task indexed by file descriptor. All zero bytes are a valid empty table,
+/// as tasks are created in zeroed pages.
+#[derive(Clone, Default)]
+pub struct FileTable {
+    files: [Option<Arc<dyn File>>; MAX_FILES],
+}
+
+impl FileTable {
+    pub const fn empty() -> Self {
+        Self {
+            files: [NO_FILE; MAX_FILES],
+        }
+    }
+
+    /// Table with stdin, stdout and stderr sharing a single console file
+    pub fn with_console() -> Self {
+        let console: Arc<dyn File> =
+            Arc::new(InodeFile::new(Arc::new(Console), AccessMode::ReadWrite));
+
+        let mut table = Self::empty();
+        for fd in [STDIN, STDOUT, STDERR] {
+            table.files[fd] = Some(console.clone());
+        }
+
+        table
+    }
+
+    pub fn get(&self, fd: usize) -> Result<Arc<dyn File>, Errno> {
+        self.files
+            .get(fd)
+            .and_then(Option::clone)
+            .ok_or(Errno::EBADF)
+    }
+
+    /// Stores `file` under the lowest free descriptor
+    pub fn insert(&mut self, file: Arc<dyn File>) -> Result<usize, Errno> {
+        let fd = self
+            .files
+            .iter()
+            .position(Option::is_none)
+            .ok_or(Errno::EMFILE)?;
+        self.files[fd] = Some(file);
+
+        Ok(fd)
+    }
+
+    pub fn close(&mut self, fd: usize) -> Result<(), Errno> {
+        self.files
+            .get_mut(fd)
+            .and_then(Option::take)
+            .map(|_| ())
+            .ok_or(Errno::EBADF)
+    }
+
+    pub fn close_all(&mut self) {
+        for file in self.files.iter_mut() {
+            file.take();
+        }
+    }
+}
+
+impl fmt::Debug for FileTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.files
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, file)| file.is_some())
+                    .map(|(fd, _)| fd),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,316 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::str;
+
+use crate::{
+    bsp::device::memory,
+    common::{
+        fs::{FileSystem, Inode, InodeKind},
+        syscall::Errno,
+    },
+    info,
+};
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const NEWC_CRC_MAGIC: &[u8] = b"070702";
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+/// Archive built from `INITRAMFS` by build.rs, located in the image by `link.ld`
+#[used]
+#[link_section = ".initramfs"]
+static INITRAMFS_IMAGE: [u8; include_bytes!(env!("INITRAMFS_PATH")).len()] =
+    *include_bytes!(env!("INITRAMFS_PATH"));
+
+/// Read only view of a cpio archive in "newc" format
+#[derive(Copy, Clone)]
+pub struct Initramfs<'a> {
+    image: &'a [u8],
+}
+
+#[derive(Copy, Clone)]
+pub struct Entry<'a> {
+    /// Path without leading `./` or `/` and trailing `/`
+    pub name: &'a str,
+    pub mode: u32,
+    pub data: &'a [u8],
+}
+
+/// Archive exposed as a read only filesystem. Directories don't need their own entries,
+/// they exist as long as some path goes through them.
+pub struct InitramfsFs {
+    archive: Initramfs<'static>,
+}
+
+struct InitramfsInode {
+    archive: Initramfs<'static>,
+    /// Normalized path inside the archive, empty for the root
+    path: String,
+    /// `None` for directories implied by paths of other entries
+    entry: Option<Entry<'static>>,
+}
+
+pub struct Entries<'a> {
+    image: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+/// Archive embedded in the kernel image
+pub fn initramfs() -> Initramfs<'static> {
+    Initramfs::new(memory::initramfs())
+}
+
+const fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+fn parse_hex(field: &[u8]) -> Result<u32, &'static str> {
+    let field = str::from_utf8(field).map_err(|_| "initramfs: header field is not ascii")?;
+    u32::from_str_radix(field, 16).map_err(|_| "initramfs: header field is not a hex number")
+}
+
+impl<'a> Initramfs<'a> {
+    pub const fn new(image: &'a [u8]) -> Self {
+        Self { image }
+    }
+
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            image: self.image,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Finds entry by its path, leading `/` and `./` are ignored
+    pub fn open(&self, path: &str) -> Result<Entry<'a>, &'static str> {
+        let path = normalize(path);
+        for entry in self.entries() {
+            let entry = entry?;
+            if entry.name == path {
+                return Ok(entry);
+            }
+        }
+
+        Err("initramfs: no such file")
+    }
+
+    pub fn print_status(&self) {
+        info!("initramfs:");
+        info!("  - size: {} bytes", self.image.len());
+        for entry in self.entries() {
+            match entry {
+                Ok(entry) if entry.is_dir() => info!("  - {}/", entry.name),
+                Ok(entry) => info!("  - {} ({} bytes)", entry.name, entry.data.len()),
+                Err(e) => info!("  - corrupted: {}", e),
+            }
+        }
+    }
+}
+
+/// Archives made with `find . | cpio -o -H newc` name entries `./path` and include `.`, which
+/// becomes an empty path
+fn normalize(path: &str) -> &str {
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    match path.trim_end_matches('/') {
+        "." => "",
+        path => path,
+    }
+}
+
+impl<'a> Entries<'a> {
+    fn parse_entry(&mut self) -> Result<Option<Entry<'a>>, &'static str> {
+        let header = self
+            .image
+            .get(self.offset..self.offset + HEADER_SIZE)
+            .ok_or("initramfs: truncated header")?;
+        let magic = &header[..6];
+        if magic != NEWC_MAGIC && magic != NEWC_CRC_MAGIC {
+            return Err("initramfs: bad magic, only newc archives are supported");
+        }
+        // Fields follow the magic as 8 hex digits each: ino, mode, uid, gid, nlink, mtime,
+        // filesize, devmajor, devminor, rdevmajor, rdevminor, namesize, check
+        let field = |idx: usize| parse_hex(&header[6 + idx * 8..6 + (idx + 1) * 8]);
+        let mode = field(1)?;
+        let file_size = field(6)? as usize;
+        let name_size = field(11)? as usize;
+
+        let name_start = self.offset + HEADER_SIZE;
+        let name = self
+            .image
+            .get(name_start..name_start + name_size)
+            .ok_or("initramfs: truncated name")?;
+        // Name size includes terminating NUL
+        let name = name.split_last().map(|(_, name)| name).unwrap_or(name);
+        let name = str::from_utf8(name).map_err(|_| "initramfs: name is not utf-8")?;
+        let name = normalize(name);
+
+        let data_start = align4(name_start + name_size);
+        let data = self
+            .image
+            .get(data_start..data_start + file_size)
+            .ok_or("initramfs: truncated file")?;
+        self.offset = align4(data_start + file_size);
+
+        if name == TRAILER {
+            return Ok(None);
+        }
+
+        Ok(Some(Entry { name, mode, data }))
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            match self.parse_entry() {
+                // Archive root, `.` entry
+                Ok(Some(entry)) if entry.name.is_empty() => continue,
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl Entry<'_> {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+}
+
+impl InitramfsFs {
+    pub const fn new(archive: Initramfs<'static>) -> Self {
+        Self { archive }
+    }
+}
+
+impl FileSystem for InitramfsFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(InitramfsInode {
+            archive: self.archive,
+            path: String::new(),
+            entry: None,
+        })
+    }
+}
+
+impl InitramfsInode {
+    /// Iterates paths of valid entries directly under this inode
+    fn children(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.archive
+            .entries()
+            .filter_map(Result::ok)
+            .map(|entry| entry.name)
+            .filter_map(move |name| {
+                if self.path.is_empty() {
+                    Some(name)
+                } else {
+                    name.strip_prefix(self.path.as_str())?.strip_prefix('/')
+                }
+            })
+            .filter(|name| !name.is_empty())
+    }
+}
+
+impl Inode for InitramfsInode {
+    fn kind(&self) -> InodeKind {
+        match self.entry {
+            Some(entry) if !entry.is_dir() => InodeKind::File,
+            _ => InodeKind::Directory,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.entry.map(|entry| entry.data.len()).unwrap_or(0)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        if self.kind() == InodeKind::Directory {
+            return Err(Errno::EISDIR);
+        }
+
+        let data = self.entry.map(|entry| entry.data).unwrap_or(&[]);
+        let remaining = data.get(offset..).unwrap_or(&[]);
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::EROFS)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, Errno> {
+        if self.kind() != InodeKind::Directory {
+            return Err(Errno::ENOTDIR);
+        }
+
+        let path = if self.path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.path, name)
+        };
+
+        let entry = match self.archive.open(&path) {
+            Ok(entry) => Some(entry),
+            Err(_)
+                if self
+                    .children()
+                    .any(|child| child.starts_with(&format!("{}/", name))) =>
+            {
+                None
+            }
+            Err(_) => return Err(Errno::ENOENT),
+        };
+
+        Ok(Arc::new(Self {
+            archive: self.archive,
+            path,
+            entry,
+        }))
+    }
+
+    fn list(&self) -> Result<Vec<String>, Errno> {
+        if self.kind() != InodeKind::Directory {
+            return Err(Errno::ENOTDIR);
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for child in self.children() {
+            let name = child.split('/').next().unwrap_or(child);
+            if !names.iter().any(|existing| existing == name) {
+                names.push(name.to_string());
+            }
+        }
+
+        Ok(names)
+    }
+}
@@ -0,0 +1,253 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::common::{
+    fs::{
+        devfs::DevFs,
+        initramfs::{initramfs, InitramfsFs},
+    },
+    sync::{IRQSpinLock, Mutex},
+    syscall::Errno,
+};
+
+pub mod console;
+pub mod devfs;
+pub mod file_table;
+pub mod initramfs;
+
+pub const PATH_MAX: usize = 256;
+
+/// Mask of the access mode bits in `open` flags
+const O_ACCMODE: u64 = 0b11;
+
+static MOUNTS: IRQSpinLock<Vec<Mount>> = IRQSpinLock::new(Vec::new());
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InodeKind {
+    File,
+    Directory,
+    CharDevice,
+}
+
+/// Target of `File::seek`, mirrors `lseek` whence values
+#[derive(Copy, Clone, Debug)]
+pub enum SeekFrom {
+    Start(usize),
+    Current(isize),
+    End(isize),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AccessMode {
+    ReadOnly = 0,
+    WriteOnly = 1,
+    ReadWrite = 2,
+}
+
+/// Node of a filesystem tree. Devices ignore the offsets.
+pub trait Inode: Send + Sync {
+    fn kind(&self) -> InodeKind;
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::EINVAL)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::EINVAL)
+    }
+
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, Errno> {
+        Err(Errno::ENOTDIR)
+    }
+
+    /// Names of directory children
+    fn list(&self) -> Result<Vec<String>, Errno> {
+        Err(Errno::ENOTDIR)
+    }
+}
+
+/// Open file description, descriptors copied by `spawn` share it together with the offset
+pub trait File: Send + Sync {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, Errno>;
+    fn write(&self, buf: &[u8]) -> Result<usize, Errno>;
+
+    fn seek(&self, _pos: SeekFrom) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+}
+
+pub trait FileSystem: Send + Sync {
+    fn root(&self) -> Arc<dyn Inode>;
+}
+
+struct Mount {
+    path: Vec<String>,
+    fs: Arc<dyn FileSystem>,
+}
+
+/// Reads and writes an inode at the offset moved by each access
+pub struct InodeFile {
+    inode: Arc<dyn Inode>,
+    mode: AccessMode,
+    offset: IRQSpinLock<usize>,
+}
+
+impl AccessMode {
+    pub fn from_flags(flags: u64) -> Result<Self, Errno> {
+        match flags & O_ACCMODE {
+            0 => Ok(Self::ReadOnly),
+            1 => Ok(Self::WriteOnly),
+            2 => Ok(Self::ReadWrite),
+            _ => Err(Errno::EINVAL),
+        }
+    }
+
+    pub fn readable(self) -> bool {
+        self != Self::WriteOnly
+    }
+
+    pub fn writable(self) -> bool {
+        self != Self::ReadOnly
+    }
+}
+
+impl InodeFile {
+    pub fn new(inode: Arc<dyn Inode>, mode: AccessMode) -> Self {
+        Self {
+            inode,
+            mode,
+            offset: IRQSpinLock::new(0),
+        }
+    }
+}
+
+impl File for InodeFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, Errno> {
+        if !self.mode.readable() {
+            return Err(Errno::EBADF);
+        }
+        if self.inode.kind() == InodeKind::Directory {
+            return Err(Errno::EISDIR);
+        }
+
+        // Device reads may block, so the lock isn't held during the access
+        let offset = self.offset.map_locked(|offset| *offset);
+        let read = self.inode.read_at(offset, buf)?;
+        self.offset.map_locked(|offset| *offset += read);
+
+        Ok(read)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, Errno> {
+        if !self.mode.writable() {
+            return Err(Errno::EBADF);
+        }
+
+        let offset = self.offset.map_locked(|offset| *offset);
+        let written = self.inode.write_at(offset, buf)?;
+        self.offset.map_locked(|offset| *offset += written);
+
+        Ok(written)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, Errno> {
+        if self.inode.kind() != InodeKind::File {
+            return Err(Errno::ESPIPE);
+        }
+
+        self.offset.map_locked(|offset| {
+            let (base, delta) = match pos {
+                SeekFrom::Start(pos) => (pos, 0),
+                SeekFrom::Current(delta) => (*offset, delta),
+                SeekFrom::End(delta) => (self.inode.size(), delta),
+            };
+            let target = if delta < 0 {
+                base.checked_sub(delta.unsigned_abs())
+            } else {
+                base.checked_add(delta as usize)
+            };
+
+            *offset = target.ok_or(Errno::EINVAL)?;
+            Ok(*offset)
+        })
+    }
+}
+
+/// Splits path into its components, `.` and `..` are resolved lexically. Relative paths start
+/// at the root, as tasks have no working directory.
+fn components(path: &str) -> Result<Vec<&str>, Errno> {
+    if path.len() > PATH_MAX {
+        return Err(Errno::ENAMETOOLONG);
+    }
+
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+
+    Ok(components)
+}
+
+/// Attaches `fs` at `path`, which doesn't have to exist in the parent filesystem
+pub fn mount(path: &str, fs: Arc<dyn FileSystem>) -> Result<(), &'static str> {
+    let path: Vec<String> = components(path)
+        .map_err(|_| "mount path is too long")?
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    MOUNTS.map_locked(|mounts| {
+        if mounts.iter().any(|mount| mount.path == path) {
+            return Err("path already has a filesystem mounted");
+        }
+
+        mounts.push(Mount { path, fs });
+        Ok(())
+    })
+}
+
+/// Finds inode under `path`, starting at the root of the most specific mount
+pub fn lookup(path: &str) -> Result<Arc<dyn Inode>, Errno> {
+    let components = components(path)?;
+
+    let (root, depth) = MOUNTS.map_locked(|mounts| {
+        mounts
+            .iter()
+            .filter(|mount| {
+                mount.path.len() <= components.len()
+                    && mount.path.iter().zip(&components).all(|(a, b)| a == b)
+            })
+            .max_by_key(|mount| mount.path.len())
+            .map(|mount| (mount.fs.root(), mount.path.len()))
+            .ok_or(Errno::ENOENT)
+    })?;
+
+    components[depth..]
+        .iter()
+        .try_fold(root, |inode, name| inode.lookup(name))
+}
+
+pub fn open(path: &str, mode: AccessMode) -> Result<Arc<dyn File>, Errno> {
+    let inode = lookup(path)?;
+    if inode.kind() == InodeKind::Directory && mode.writable() {
+        return Err(Errno::EISDIR);
+    }
+
+    Ok(Arc::new(InodeFile::new(inode, mode)))
+}
+
+/// Mounts initramfs as the root filesystem and devfs under `/dev`
+pub fn init() -> Result<(), &'static str> {
+    mount("/", Arc::new(InitramfsFs::new(initramfs())))?;
+    devfs::init()?;
+    mount("/dev", Arc::new(DevFs))
+}
@@ -0,0 +1,58 @@
+use core::fmt;
+
+use crate::common::{
+    exception::PrivilegeLevel,
+    memory::{Address, Virtual},
+    scheduler::SCHEDULER,
+    syscall::Errno,
+};
+
+/// Exit code of a task killed because of an illegal memory access
+pub const FAULT_EXIT_CODE: i64 = -(Errno::EFAULT as i64);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FaultKind {
+    /// Page is not mapped
+    Translation,
+    AccessFlag,
+    /// Page is mapped, but the access isn't allowed
+    Permission,
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+pub struct PageFault {
+    pub addr: Address<Virtual>,
+    pub kind: FaultKind,
+    pub access: AccessKind,
+    pub level: PrivilegeLevel,
+}
+
+impl fmt::Display for PageFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} fault on {:?} of {} in {} mode",
+            self.kind, self.access, self.addr, self.level
+        )
+    }
+}
+
+/// Maps missing pages of current task's regions, fails when the access is illegal
+pub fn handle_page_fault(fault: &PageFault) -> Result<(), &'static str> {
+    match fault.kind {
+        FaultKind::Translation => {
+            SCHEDULER.map_current(|task| task.mm.handle_fault(fault.addr, fault.access))
+        }
+        // Every descriptor is created with access flag already set
+        FaultKind::AccessFlag => Err("unexpected access flag fault"),
+        FaultKind::Permission => Err("access violates page permissions"),
+        FaultKind::Other => Err("unsupported fault"),
+    }
+}
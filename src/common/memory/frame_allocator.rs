@@ -0,0 +1,184 @@
+use crate::{
+    bsp::device::memory::{
+        map::user::{LOW_MEMORY, PAGE_COUNT},
+        mmu::KernelGranule,
+    },
+    common::{
+        memory::{mmu::descriptors::PageSliceDescriptor, Address, Physical},
+        sync::IRQSpinLock,
+    },
+    info,
+};
+
+const BITMAP_WORDS: usize = (PAGE_COUNT + u64::BITS as usize - 1) / u64::BITS as usize;
+
+pub static FRAME_ALLOCATOR: IRQSpinLock<BitmapFrameAllocator<BITMAP_WORDS>> =
+    IRQSpinLock::new(BitmapFrameAllocator::new(LOW_MEMORY, PAGE_COUNT));
+
+#[derive(Copy, Clone, Debug)]
+pub struct FrameAllocatorStats {
+    pub base: Address<Physical>,
+    pub total: usize,
+    pub used: usize,
+    pub peak: usize,
+}
+
+/// One bit per physical frame, set bit means frame is in use
+pub struct BitmapFrameAllocator<const WORDS: usize> {
+    base: Address<Physical>,
+    frame_count: usize,
+    bitmap: [u64; WORDS],
+    used: usize,
+    peak: usize,
+    /// Every frame below this index is known to be in use
+    first_free: usize,
+}
+
+impl FrameAllocatorStats {
+    pub fn free(&self) -> usize {
+        self.total - self.used
+    }
+
+    /// Printed from a copy, as logging under `FRAME_ALLOCATOR` could take locks which are
+    /// held while allocating frames
+    pub fn print_status(&self) {
+        let kib = KernelGranule::SIZE / 1024;
+
+        info!("physical frames:");
+        info!(
+            "  - range: {}..{}",
+            self.base,
+            self.base + ((self.total << KernelGranule::SHIFT) - 1)
+        );
+        info!(
+            "  - used: {}/{} frames ({} KiB free, peak {} KiB)",
+            self.used,
+            self.total,
+            self.free() * kib,
+            self.peak * kib
+        );
+    }
+}
+
+impl<const WORDS: usize> BitmapFrameAllocator<WORDS> {
+    const BITS: usize = u64::BITS as usize;
+
+    pub const fn new(base: Address<Physical>, frame_count: usize) -> Self {
+        assert!(frame_count <= WORDS * Self::BITS);
+        assert!(base.addr() & KernelGranule::MASK == 0);
+
+        Self {
+            base,
+            frame_count,
+            bitmap: [0; WORDS],
+            used: 0,
+            peak: 0,
+            first_free: 0,
+        }
+    }
+
+    fn is_used(&self, idx: usize) -> bool {
+        self.bitmap[idx / Self::BITS] & (1 << (idx % Self::BITS)) != 0
+    }
+
+    fn mark(&mut self, start: usize, count: usize, used: bool) {
+        for idx in start..start + count {
+            let bit = 1 << (idx % Self::BITS);
+            if used {
+                self.bitmap[idx / Self::BITS] |= bit;
+            } else {
+                self.bitmap[idx / Self::BITS] &= !bit;
+            }
+        }
+    }
+
+    fn find_free_run(&self, count: usize) -> Option<usize> {
+        let mut run_start = self.first_free;
+        let mut idx = self.first_free;
+
+        while idx < self.frame_count {
+            if idx % Self::BITS == 0 && self.bitmap[idx / Self::BITS] == u64::MAX {
+                idx += Self::BITS;
+                run_start = idx;
+                continue;
+            }
+
+            if self.is_used(idx) {
+                run_start = idx + 1;
+            } else if idx + 1 - run_start == count {
+                return Some(run_start);
+            }
+
+            idx += 1;
+        }
+
+        None
+    }
+
+    fn frame_index(&self, addr: Address<Physical>) -> Result<usize, &'static str> {
+        if addr.addr() < self.base.addr() || addr.addr() & KernelGranule::MASK != 0 {
+            return Err("frame address outside of allocator range");
+        }
+
+        let idx = (addr.addr() - self.base.addr()) >> KernelGranule::SHIFT;
+        if idx >= self.frame_count {
+            return Err("frame address outside of allocator range");
+        }
+
+        Ok(idx)
+    }
+
+    pub fn alloc_frames(
+        &mut self,
+        num_frames: usize,
+    ) -> Result<PageSliceDescriptor<Physical>, &'static str> {
+        if num_frames == 0 {
+            return Err("alloc_frames: num_frames = 0");
+        }
+
+        let start = self.find_free_run(num_frames).ok_or("out of memory")?;
+
+        self.mark(start, num_frames, true);
+        self.used += num_frames;
+        self.peak = self.peak.max(self.used);
+        if start == self.first_free {
+            self.first_free = start + num_frames;
+        }
+
+        Ok(PageSliceDescriptor::from_addr(
+            self.base + (start << KernelGranule::SHIFT),
+            num_frames,
+        ))
+    }
+
+    pub fn free_frames(
+        &mut self,
+        frames: PageSliceDescriptor<Physical>,
+    ) -> Result<(), &'static str> {
+        let start = self.frame_index(frames.start_addr())?;
+        let count = frames.num_pages();
+
+        if start + count > self.frame_count {
+            return Err("free_frames: frames outside of allocator range");
+        }
+
+        if (start..start + count).any(|idx| !self.is_used(idx)) {
+            return Err("free_frames: frame is not allocated");
+        }
+
+        self.mark(start, count, false);
+        self.used -= count;
+        self.first_free = self.first_free.min(start);
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> FrameAllocatorStats {
+        FrameAllocatorStats {
+            base: self.base,
+            total: self.frame_count,
+            used: self.used,
+            peak: self.peak,
+        }
+    }
+}
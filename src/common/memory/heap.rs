@@ -0,0 +1,189 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem::{align_of, size_of},
+    ptr::null_mut,
+};
+
+use crate::{
+    bsp::device::memory::{map::heap::KERNEL_HEAP_SIZE, mmu::KernelGranule},
+    common::{
+        memory::{mmu::alloc_pages, Address, Virtual},
+        sync::{IRQSpinLock, Mutex},
+    },
+    info,
+};
+
+#[global_allocator]
+pub static KERNEL_HEAP: KernelHeap = KernelHeap::new();
+
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// First-fit allocator over an address ordered list of free blocks
+pub struct LinkedListHeap {
+    head: FreeBlock,
+    start: usize,
+    size: usize,
+    used: usize,
+}
+
+pub struct KernelHeap {
+    inner: IRQSpinLock<LinkedListHeap>,
+}
+
+unsafe impl Send for LinkedListHeap {}
+
+const MIN_BLOCK_SIZE: usize = size_of::<FreeBlock>();
+
+const fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+impl LinkedListHeap {
+    pub const fn empty() -> Self {
+        Self {
+            head: FreeBlock {
+                size: 0,
+                next: null_mut(),
+            },
+            start: 0,
+            size: 0,
+            used: 0,
+        }
+    }
+
+    pub unsafe fn init(&mut self, start: usize, size: usize) {
+        if self.size != 0 {
+            panic!("Kernel heap is already initialized");
+        }
+
+        let aligned_start = align_up(start, align_of::<FreeBlock>());
+        self.start = aligned_start;
+        self.size = size - (aligned_start - start);
+        self.insert_free_block(self.start, self.size);
+    }
+
+    fn block_layout(layout: Layout) -> (usize, usize) {
+        let align = layout.align().max(align_of::<FreeBlock>());
+        let size = align_up(layout.size().max(MIN_BLOCK_SIZE), align_of::<FreeBlock>());
+
+        (size, align)
+    }
+
+    /// Inserts block keeping the list sorted and merges it with adjacent neighbours
+    unsafe fn insert_free_block(&mut self, addr: usize, size: usize) {
+        let head: *mut FreeBlock = &mut self.head;
+        let mut prev = head;
+        while !(*prev).next.is_null() && ((*prev).next as usize) < addr {
+            prev = (*prev).next;
+        }
+
+        let next = (*prev).next;
+        let block = addr as *mut FreeBlock;
+        block.write(FreeBlock { size, next });
+        (*prev).next = block;
+
+        if !next.is_null() && addr + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        if prev != head && prev as usize + (*prev).size == addr {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        }
+    }
+
+    pub fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Self::block_layout(layout);
+
+        unsafe {
+            let mut prev: *mut FreeBlock = &mut self.head;
+            while !(*prev).next.is_null() {
+                let block = (*prev).next;
+                let start = block as usize;
+                let end = start + (*block).size;
+
+                let mut alloc_start = align_up(start, align);
+                if alloc_start != start && alloc_start - start < MIN_BLOCK_SIZE {
+                    alloc_start = align_up(start + MIN_BLOCK_SIZE, align);
+                }
+
+                let fits = alloc_start.checked_add(size).filter(|&alloc_end| {
+                    alloc_end <= end && (alloc_end == end || end - alloc_end >= MIN_BLOCK_SIZE)
+                });
+
+                if let Some(alloc_end) = fits {
+                    (*prev).next = (*block).next;
+
+                    if alloc_start > start {
+                        self.insert_free_block(start, alloc_start - start);
+                    }
+                    if end > alloc_end {
+                        self.insert_free_block(alloc_end, end - alloc_end);
+                    }
+
+                    self.used += size;
+
+                    return alloc_start as *mut u8;
+                }
+
+                prev = block;
+            }
+        }
+
+        null_mut()
+    }
+
+    pub unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Self::block_layout(layout);
+
+        self.insert_free_block(ptr as usize, size);
+        self.used -= size;
+    }
+}
+
+impl KernelHeap {
+    pub const fn new() -> Self {
+        Self {
+            inner: IRQSpinLock::new(LinkedListHeap::empty()),
+        }
+    }
+
+    /// Logs outside the heap lock, logging may allocate under locks of its own
+    pub fn print_status(&self) {
+        let (start, size, used) = self
+            .inner
+            .map_locked(|heap| (heap.start, heap.size, heap.used));
+
+        info!("kernel heap:");
+        info!(
+            "  - range: {}..{}",
+            Address::<Virtual>::new(start),
+            Address::<Virtual>::new(start + size - 1)
+        );
+        info!("  - used: {} KiB / {} KiB", used / 1024, size / 1024);
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.inner.map_locked(|heap| heap.alloc(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.map_locked(|heap| heap.dealloc(ptr, layout))
+    }
+}
+
+pub fn init_kernel_heap() -> Result<(), &'static str> {
+    let vpages = alloc_pages(KERNEL_HEAP_SIZE >> KernelGranule::SHIFT)?;
+
+    KERNEL_HEAP
+        .inner
+        .map_locked(|heap| unsafe { heap.init(vpages.start_addr().addr(), vpages.size()) });
+
+    Ok(())
+}
@@ -4,7 +4,7 @@ use derive_more::Display;
 
 use crate::{
     bsp::device::memory::mmu::KernelGranule,
-    common::memory::{Address, AddressType, Physical, Virtual},
+    common::memory::{phys_to_virt, virt_to_phys, Address, AddressType, Physical, Virtual},
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Display)]
@@ -20,6 +20,7 @@ pub enum MemoryAttributes {
 pub enum AccessPermissions {
     RX,
     RW,
+    RX_EL0,
     RW_EL0,
 }
 
@@ -134,10 +135,20 @@ impl<A: AddressType> PageSliceDescriptor<A> {
     }
 }
 
+/// Valid only for pages in the kernel linear mapping
 impl From<PageSliceDescriptor<Virtual>> for PageSliceDescriptor<Physical> {
     fn from(desc: PageSliceDescriptor<Virtual>) -> Self {
         Self {
-            start: Address::new(desc.start.addr()),
+            start: virt_to_phys(desc.start),
+            num_pages: desc.num_pages,
+        }
+    }
+}
+
+impl From<PageSliceDescriptor<Physical>> for PageSliceDescriptor<Virtual> {
+    fn from(desc: PageSliceDescriptor<Physical>) -> Self {
+        Self {
+            start: phys_to_virt(desc.start),
             num_pages: desc.num_pages,
         }
     }
@@ -6,7 +6,7 @@ use crate::{
             Physical,
             Virtual,
         },
-        sync::{InitStateLock, ReadWriteLock},
+        sync::{ReadWriteLock, RwSpinLock},
     },
     info,
     print,
@@ -25,8 +25,7 @@ pub struct MappingRecord {
     items: [Option<MappingRecordEntry>; 12],
 }
 
-pub static KERNEL_MAPPING_RECORD: InitStateLock<MappingRecord> =
-    InitStateLock::new(MappingRecord::new());
+pub static KERNEL_MAPPING_RECORD: RwSpinLock<MappingRecord> = RwSpinLock::new(MappingRecord::new());
 
 impl MappingRecordEntry {
     pub fn new(
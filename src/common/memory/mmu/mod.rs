@@ -19,7 +19,7 @@ use crate::{
             Physical,
             Virtual,
         },
-        statics::KERNEL_TABLES,
+        statics::{FRAME_ALLOCATOR, KERNEL_TABLES},
         sync::Mutex,
     },
     statics,
@@ -30,9 +30,15 @@ pub mod mapping;
 pub mod translation_table;
 
 pub trait MemoryManagementUnit {
+    /// Turns MMU on with tables mapping kernel image at both its load and link addresses
     unsafe fn enable_mmu_and_caching(
         &self,
-        translation_table_base_addr: Address<Physical>,
+        boot_table_base_addr: Address<Physical>,
+    ) -> Result<(), &'static str>;
+    /// Replaces boot tables with complete kernel tables, dropping the identity mapping
+    unsafe fn switch_kernel_tables(
+        &self,
+        kernel_table_base_addr: Address<Physical>,
     ) -> Result<(), &'static str>;
     fn is_enabled(&self) -> bool;
 }
@@ -148,8 +154,33 @@ pub fn map_kernel_mmio(
     Ok(addr + offset)
 }
 
+/// Allocates physically contiguous pages, they're accessed through the kernel linear mapping
+pub fn alloc_pages(num_pages: usize) -> Result<PageSliceDescriptor<Virtual>, &'static str> {
+    let ppages = FRAME_ALLOCATOR.map_locked(|allocator| allocator.alloc_frames(num_pages))?;
+    let vpages: PageSliceDescriptor<Virtual> = ppages.into();
+
+    crate::trace!("allocated {} page(s) at {}", num_pages, vpages.start_addr());
+
+    Ok(vpages)
+}
+
+/// Returns frames to the allocator. Unlike before the linear mapping, pages stay mapped RW in
+/// kernel tables, so a use after free doesn't fault and writes to whoever gets the frames next,
+/// user tasks included. Unmapping would mean splitting the linear mapping on every free.
+pub fn free_pages(vpages: PageSliceDescriptor<Virtual>) -> Result<(), &'static str> {
+    crate::trace!(
+        "freed {} page(s) at {}",
+        vpages.num_pages(),
+        vpages.start_addr()
+    );
+
+    FRAME_ALLOCATOR.map_locked(|allocator| allocator.free_frames(vpages.into()))
+}
+
 pub fn next_free_page() -> Result<Address<Virtual>, &'static str> {
-    Ok(KERNEL_TABLES
-        .map_locked(|tables| tables.next_user_page_slice(1))?
-        .start_addr())
+    Ok(alloc_pages(1)?.start_addr())
+}
+
+pub fn free_page(addr: Address<Virtual>) -> Result<(), &'static str> {
+    free_pages(PageSliceDescriptor::from_addr(addr, 1))
 }
@@ -18,9 +18,10 @@ pub trait TranslationTable {
         &mut self,
         num_pages: usize,
     ) -> Result<PageSliceDescriptor<Virtual>, &'static str>;
-    fn next_user_page_slice(
+    unsafe fn unmap_pages(
         &mut self,
-        num_pages: usize,
-    ) -> Result<PageSliceDescriptor<Virtual>, &'static str>;
+        vpages: PageSliceDescriptor<Virtual>,
+    ) -> Result<(), &'static str>;
+    fn translate(&self, addr: Address<Virtual>) -> Result<Address<Physical>, &'static str>;
     fn is_page_slice_mmio(&self, pages: PageSliceDescriptor<Virtual>) -> bool;
 }
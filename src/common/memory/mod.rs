@@ -1,8 +1,12 @@
 use core::{fmt, marker::PhantomData, ops::Add};
 
-use crate::common::align_down;
+use crate::{bsp::device::memory::mmu::KERNEL_VIRT_OFFSET, common::align_down};
 
+pub mod fault;
+pub mod frame_allocator;
+pub mod heap;
 pub mod mmu;
+pub mod user_space;
 
 pub trait AddressType: Copy + PartialEq {}
 
@@ -41,6 +45,16 @@ impl<A: AddressType> Address<A> {
     }
 }
 
+/// Translates address from the kernel high half, where kernel image and RAM are mapped linearly
+pub const fn virt_to_phys(addr: Address<Virtual>) -> Address<Physical> {
+    Address::new(addr.addr() - KERNEL_VIRT_OFFSET)
+}
+
+/// Returns address under which kernel can access physical memory
+pub const fn phys_to_virt(addr: Address<Physical>) -> Address<Virtual> {
+    Address::new(addr.addr() + KERNEL_VIRT_OFFSET)
+}
+
 impl<A: AddressType> const From<Address<A>> for usize {
     fn from(item: Address<A>) -> Self {
         item.addr
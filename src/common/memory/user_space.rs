@@ -0,0 +1,494 @@
+use core::{mem::size_of, ptr};
+
+use crate::{
+    arch::arch_impl::memory::mmu::{
+        invalidate_tlb_asid,
+        switch_user_table,
+        translation_table::UserTranslationTable,
+        NUM_ASIDS,
+    },
+    bsp::device::memory::mmu::{KernelGranule, UserAddrSpace},
+    common::{
+        memory::{
+            fault::AccessKind,
+            mmu::{
+                alloc_pages,
+                descriptors::{
+                    AccessPermissions,
+                    Attributes,
+                    Execute,
+                    MemoryAttributes,
+                    PageSliceDescriptor,
+                },
+                free_page,
+                free_pages,
+                next_free_page,
+                translation_table::TranslationTable,
+            },
+            phys_to_virt,
+            virt_to_phys,
+            Address,
+            Virtual,
+        },
+        sync::{IRQSpinLock, Mutex},
+    },
+};
+
+const MAX_USER_REGIONS: usize = 16;
+const ASID_WORDS: usize = NUM_ASIDS / u64::BITS as usize;
+const TABLE_PAGES: usize =
+    (size_of::<UserTranslationTable>() + KernelGranule::MASK) >> KernelGranule::SHIFT;
+/// Lowest address `alloc_region` hands out, leaves space below for program images
+const MMAP_BASE: usize = 0x1000_0000;
+
+static ASIDS: IRQSpinLock<AsidAllocator> = IRQSpinLock::new(AsidAllocator::new());
+
+struct AsidAllocator {
+    used: [u64; ASID_WORDS],
+}
+
+/// Range of pages accessible from EL0. Region with `num_pages == 0` is an empty slot.
+#[derive(Default, Copy, Clone, Debug)]
+#[repr(C)]
+pub struct UserRegion {
+    pub start: usize,
+    pub num_pages: usize,
+    pub writable: bool,
+    pub executable: bool,
+}
+
+/// Memory of a single user process, translated through TTBR0 with its own ASID.
+/// All zero bytes describe a task without user space, so it can live inside the zeroed task page.
+#[derive(Default, Debug)]
+#[repr(C)]
+pub struct UserAddressSpace {
+    /// Kernel address of the `UserTranslationTable`
+    table: usize,
+    asid: u16,
+    regions: [UserRegion; MAX_USER_REGIONS],
+}
+
+impl AsidAllocator {
+    const fn new() -> Self {
+        let mut used = [0; ASID_WORDS];
+        // Reserved for tasks without user space
+        used[0] = 1;
+
+        Self { used }
+    }
+
+    fn alloc(&mut self) -> Option<u16> {
+        let (idx, word) = self
+            .used
+            .iter_mut()
+            .enumerate()
+            .find(|(_, word)| **word != u64::MAX)?;
+
+        let bit = word.trailing_ones() as usize;
+        *word |= 1 << bit;
+
+        Some((idx * u64::BITS as usize + bit) as u16)
+    }
+
+    fn free(&mut self, asid: u16) {
+        let asid = asid as usize;
+        self.used[asid / u64::BITS as usize] &= !(1 << (asid % u64::BITS as usize));
+    }
+}
+
+impl UserRegion {
+    pub fn end(&self) -> usize {
+        self.start + (self.num_pages << KernelGranule::SHIFT)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.num_pages == 0
+    }
+
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        start < self.end() && self.start < end
+    }
+
+    fn allows(&self, access: AccessKind) -> bool {
+        match access {
+            AccessKind::Read => true,
+            AccessKind::Write => self.writable,
+            AccessKind::Execute => self.executable,
+        }
+    }
+
+    fn attributes(&self) -> Attributes {
+        Attributes {
+            memory: MemoryAttributes::CacheableDRAM,
+            access: if self.writable {
+                AccessPermissions::RW_EL0
+            } else {
+                AccessPermissions::RX_EL0
+            },
+            execute: if self.executable {
+                Execute::Allow
+            } else {
+                Execute::Never
+            },
+        }
+    }
+}
+
+impl UserAddressSpace {
+    pub const fn empty() -> Self {
+        Self {
+            table: 0,
+            asid: 0,
+            regions: [UserRegion {
+                start: 0,
+                num_pages: 0,
+                writable: false,
+                executable: false,
+            }; MAX_USER_REGIONS],
+        }
+    }
+
+    pub fn new() -> Result<Self, &'static str> {
+        let pages = alloc_pages(TABLE_PAGES)?;
+        let asid = match ASIDS.map_locked(|asids| asids.alloc()) {
+            Some(asid) => asid,
+            None => {
+                free_pages(pages)?;
+                return Err("no free ASID");
+            }
+        };
+
+        let mut space = Self::empty();
+        space.table = pages.start_addr().addr();
+        space.asid = asid;
+
+        unsafe { (space.table as *mut u8).write_bytes(0, pages.size()) };
+        space.table_mut().init();
+
+        Ok(space)
+    }
+
+    fn table(&self) -> &UserTranslationTable {
+        unsafe { &*(self.table as *const UserTranslationTable) }
+    }
+
+    fn table_mut(&mut self) -> &mut UserTranslationTable {
+        unsafe { &mut *(self.table as *mut UserTranslationTable) }
+    }
+
+    /// Loads translation table into TTBR0, called when task is switched in
+    pub fn activate(&self) {
+        if self.table == 0 {
+            switch_user_table(None)
+        } else {
+            switch_user_table(Some((self.table().base_addr(), self.asid)))
+        }
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = &UserRegion> {
+        self.regions.iter().filter(|region| !region.is_empty())
+    }
+
+    fn insert(&mut self, region: UserRegion) -> Result<(), &'static str> {
+        if region.is_empty() {
+            return Err("cannot insert empty user region");
+        }
+        if region.start & KernelGranule::MASK != 0 {
+            return Err("user region is not page aligned");
+        }
+        if region.end() > UserAddrSpace::SIZE {
+            return Err("user region exceeds address space");
+        }
+        if self
+            .regions()
+            .any(|other| other.overlaps(region.start, region.end()))
+        {
+            return Err("user region overlaps existing one");
+        }
+
+        let slot = self
+            .regions
+            .iter_mut()
+            .find(|slot| slot.is_empty())
+            .ok_or("too many user regions")?;
+        *slot = region;
+
+        Ok(())
+    }
+
+    /// Adds region without backing it with frames, its pages get mapped on first access
+    pub fn reserve_region(
+        &mut self,
+        start: Address<Virtual>,
+        num_pages: usize,
+        writable: bool,
+        executable: bool,
+    ) -> Result<UserRegion, &'static str> {
+        if self.table == 0 {
+            return Err("task has no user address space");
+        }
+
+        let region = UserRegion {
+            start: start.addr(),
+            num_pages,
+            writable,
+            executable,
+        };
+        self.insert(region)?;
+
+        Ok(region)
+    }
+
+    /// Maps `num_pages` zeroed pages at `start`
+    pub fn map_region(
+        &mut self,
+        start: Address<Virtual>,
+        num_pages: usize,
+        writable: bool,
+        executable: bool,
+    ) -> Result<(), &'static str> {
+        let region = self.reserve_region(start, num_pages, writable, executable)?;
+
+        for idx in 0..num_pages {
+            let vpage = start + (idx << KernelGranule::SHIFT);
+            if let Err(err) = self.map_page(vpage, region.attributes()) {
+                self.release_pages(start, idx);
+                self.remove(start.addr(), num_pages);
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reserves region at the lowest free address above `MMAP_BASE`
+    pub fn alloc_region(
+        &mut self,
+        num_pages: usize,
+        writable: bool,
+        executable: bool,
+    ) -> Result<Address<Virtual>, &'static str> {
+        let size = num_pages << KernelGranule::SHIFT;
+        let mut start = MMAP_BASE;
+        while let Some(region) = self
+            .regions()
+            .find(|region| region.overlaps(start, start + size))
+        {
+            start = region.end();
+        }
+
+        self.reserve_region(Address::new(start), num_pages, writable, executable)?;
+
+        Ok(Address::new(start))
+    }
+
+    /// Releases region that starts exactly at `start` and spans `num_pages`
+    pub fn unmap_region(
+        &mut self,
+        start: Address<Virtual>,
+        num_pages: usize,
+    ) -> Result<(), &'static str> {
+        self.remove(start.addr(), num_pages)
+            .ok_or("no such user region")?;
+        self.release_pages(start, num_pages);
+
+        Ok(())
+    }
+
+    fn map_page(
+        &mut self,
+        vpage: Address<Virtual>,
+        attributes: Attributes,
+    ) -> Result<(), &'static str> {
+        let frame = next_free_page()?;
+        unsafe { (frame.addr() as *mut u8).write_bytes(0, KernelGranule::SIZE) };
+
+        let result = unsafe {
+            self.table_mut().map_pages(
+                PageSliceDescriptor::from_addr(vpage, 1),
+                PageSliceDescriptor::from_addr(virt_to_phys(frame), 1),
+                attributes,
+            )
+        };
+        if result.is_err() {
+            free_page(frame)?;
+        }
+
+        result
+    }
+
+    /// Unmaps pages and returns their frames, pages that aren't mapped are skipped
+    fn release_pages(&mut self, start: Address<Virtual>, num_pages: usize) {
+        for idx in 0..num_pages {
+            let vpage = start + (idx << KernelGranule::SHIFT);
+            let frame = match self.table().translate(vpage) {
+                Ok(frame) => frame,
+                Err(_) => continue,
+            };
+
+            unsafe {
+                self.table_mut()
+                    .unmap_pages(PageSliceDescriptor::from_addr(vpage, 1))
+                    .expect("unmap user page");
+            }
+            free_page(phys_to_virt(frame)).expect("free user page");
+        }
+    }
+
+    fn remove(&mut self, start: usize, num_pages: usize) -> Option<UserRegion> {
+        let slot = self
+            .regions
+            .iter_mut()
+            .find(|slot| !slot.is_empty() && slot.start == start && slot.num_pages == num_pages)?;
+
+        Some(core::mem::take(slot))
+    }
+
+    /// Returns address under which kernel can reach user memory at `addr`, regardless of which
+    /// address space is currently active
+    pub fn kernel_address(&self, addr: Address<Virtual>) -> Result<Address<Virtual>, &'static str> {
+        if self.table == 0 {
+            return Err("task has no user address space");
+        }
+
+        Ok(phys_to_virt(self.table().translate(addr)?))
+    }
+
+    /// Resolves translation fault by mapping a fresh page, if `addr` belongs to a region that
+    /// permits `access`
+    pub fn handle_fault(
+        &mut self,
+        addr: Address<Virtual>,
+        access: AccessKind,
+    ) -> Result<(), &'static str> {
+        let region = self.region_of(addr)?;
+        if !region.allows(access) {
+            return Err("access not permitted in region");
+        }
+
+        let vpage = addr.align_down::<{ KernelGranule::SHIFT }>();
+        if self.table().translate(vpage).is_ok() {
+            return Err("page is already mapped");
+        }
+
+        crate::trace!("demand paging {}", vpage);
+
+        self.map_page(vpage, region.attributes())
+    }
+
+    fn region_of(&self, addr: Address<Virtual>) -> Result<UserRegion, &'static str> {
+        self.regions()
+            .find(|region| region.start <= addr.addr() && addr.addr() < region.end())
+            .copied()
+            .ok_or("address outside of user regions")
+    }
+
+    /// Copies `data` to `addr`, mapping untouched pages on the way. Works for any region,
+    /// including read-only ones, and regardless of which address space is active.
+    pub fn copy_to_user(
+        &mut self,
+        addr: Address<Virtual>,
+        data: &[u8],
+    ) -> Result<(), &'static str> {
+        let mut copied = 0;
+        while copied < data.len() {
+            let dst = addr + copied;
+            let vpage = dst.align_down::<{ KernelGranule::SHIFT }>();
+            if self.table().translate(vpage).is_err() {
+                let region = self.region_of(vpage)?;
+                self.map_page(vpage, region.attributes())?;
+            }
+
+            let offset = dst.addr() & KernelGranule::MASK;
+            let len = (KernelGranule::SIZE - offset).min(data.len() - copied);
+            let kernel_dst = self.kernel_address(dst)?;
+            unsafe {
+                ptr::copy_nonoverlapping(data[copied..].as_ptr(), kernel_dst.addr() as *mut u8, len)
+            };
+
+            copied += len;
+        }
+
+        Ok(())
+    }
+
+    /// Creates new address space with copies of all regions, partial copy is released on error.
+    /// Pages that weren't touched yet stay unmapped in the copy.
+    pub fn duplicate(&self) -> Result<Self, &'static str> {
+        let mut copy = Self::new()?;
+
+        for region in self.regions() {
+            let start = Address::new(region.start);
+            copy.reserve_region(start, region.num_pages, region.writable, region.executable)?;
+
+            for idx in 0..region.num_pages {
+                let vpage = start + (idx << KernelGranule::SHIFT);
+                let src = match self.kernel_address(vpage) {
+                    Ok(src) => src,
+                    Err(_) => continue,
+                };
+                copy.map_page(vpage, region.attributes())?;
+                let dst = copy.kernel_address(vpage)?;
+
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        src.addr() as *const u8,
+                        dst.addr() as *mut u8,
+                        KernelGranule::SIZE,
+                    )
+                };
+            }
+        }
+
+        Ok(copy)
+    }
+
+    /// Checks whether whole `addr..addr + len` lies in a single region with requested access
+    pub fn contains(&self, addr: usize, len: usize, write: bool, execute: bool) -> bool {
+        let end = match addr.checked_add(len) {
+            Some(end) => end,
+            None => return false,
+        };
+
+        self.regions().any(|region| {
+            region.start <= addr
+                && end <= region.end()
+                && (!write || region.allows(AccessKind::Write))
+                && (!execute || region.allows(AccessKind::Execute))
+        })
+    }
+
+    /// Frees every page, translation table and ASID. Address space must not be active.
+    /// Tasks live in raw pages, so this has to be called explicitly when task is reaped.
+    pub fn release(&mut self) {
+        if self.table == 0 {
+            return;
+        }
+
+        for idx in 0..MAX_USER_REGIONS {
+            let region = self.regions[idx];
+            if !region.is_empty() {
+                self.release_pages(Address::new(region.start), region.num_pages);
+            }
+        }
+
+        free_pages(PageSliceDescriptor::from_addr(
+            Address::new(self.table),
+            TABLE_PAGES,
+        ))
+        .expect("free user translation table");
+
+        invalidate_tlb_asid(self.asid);
+        ASIDS.map_locked(|asids| asids.free(self.asid));
+
+        self.table = 0;
+        self.asid = 0;
+        self.regions = Default::default();
+    }
+}
+
+impl Drop for UserAddressSpace {
+    fn drop(&mut self) {
+        self.release()
+    }
+}
@@ -1,14 +1,22 @@
+pub mod backtrace;
+pub mod dmesg;
 pub mod driver;
+pub mod elf;
 pub mod exception;
+pub mod fs;
 pub mod memory;
+pub mod ring_buffer;
 pub mod scheduler;
 pub mod serial_console;
+pub mod shell;
 pub mod state;
 pub mod statics;
+pub mod symbols;
 pub mod sync;
 pub mod syscall;
 pub mod task;
 pub mod time;
+pub mod tty;
 
 pub const fn align_down<const SHIFT: usize>(value: usize) -> usize {
     value & !((1 << SHIFT) - 1)
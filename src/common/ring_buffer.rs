@@ -0,0 +1,73 @@
+use core::mem::MaybeUninit;
+
+/// Fixed capacity FIFO usable in statics, callers provide locking
+pub struct RingBuffer<T: Copy, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [MaybeUninit::uninit(); N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Returns `item` back when the buffer is full
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        self.buf[(self.head + self.len) % N].write(item);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Drops the oldest item to make space when the buffer is full
+    pub fn push_overwrite(&mut self, item: T) {
+        if self.is_full() {
+            self.pop();
+        }
+
+        let _ = self.push(item);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let item = unsafe { self.buf[self.head].assume_init() };
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        Some(item)
+    }
+
+    /// Items from the oldest one, without removing them
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len).map(move |i| unsafe { self.buf[(self.head + i) % N].assume_init() })
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
@@ -1,24 +1,58 @@
-use core::mem::size_of;
+use core::{
+    mem::{self, size_of},
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use crate::{
     arch::arch_impl::{
-        cpu::exception::{
-            asynchronous::{mask_irq, unmask_irq},
-            return_from_fork,
+        cpu::{
+            exception::{
+                asynchronous::{local_irq_restore, local_irq_save, mask_irq, unmask_irq},
+                return_from_fork,
+            },
+            instructions::wfi,
+            registers::mpidr_el1::core_id_el1,
         },
         task::{CpuContext, PtRegs},
     },
-    bsp::device_driver::WrappedPointer,
+    bsp::{
+        device::{cpu::NUM_CORES, memory::mmu::KernelGranule},
+        device_driver::WrappedPointer,
+    },
     common::{
-        memory::mmu::next_free_page,
-        sync::{IRQSafeNullLock, Mutex},
+        exception::asynchronous::{IPIManager, IRQHandler},
+        fs::file_table::FileTable,
+        memory::{
+            mmu::{free_page, next_free_page},
+            user_space::UserAddressSpace,
+            Address,
+            Virtual,
+        },
+        statics::INTERRUPT_CONTROLLER,
+        sync::{assert_no_locks_held, IRQSpinLock, Mutex, WaitQueue},
         task::{Task, TaskState},
         time::scheduling::TickCallbackHandler,
     },
 };
 
 pub static SCHEDULER: Scheduler<64> = Scheduler::new();
-pub static mut INIT_TASK: Task = Task {
+static NEXT_PID: AtomicU64 = AtomicU64::new(1);
+static TASK_EXIT_QUEUE: WaitQueue = WaitQueue::new();
+
+/// Affinity of new tasks
+const ALL_CORES: u64 = (1 << NUM_CORES) - 1;
+/// Boot core ticks between load balancing passes
+const BALANCE_INTERVAL: u64 = 100;
+/// Kernel stack of a task is the rest of its page after `Task`, nothing detects an overflow
+const MIN_KERNEL_STACK_SIZE: usize = 16 * 1024;
+
+const _: () = assert!(
+    size_of::<Task>() + MIN_KERNEL_STACK_SIZE <= KernelGranule::SIZE,
+    "Task leaves too little room for the kernel stack"
+);
+
+const IDLE_TASK: Task = Task {
     context: CpuContext {
         x19: 0,
         x20: 0,
@@ -36,21 +70,43 @@ pub static mut INIT_TASK: Task = Task {
     },
     state: TaskState::Running,
     counter: 0,
-    priority: 0,
+    priority: 1,
     preempt_count: 0,
     stack: 0,
+    pid: 0,
+    parent: 0,
+    affinity: 0,
+    on_cpu: true,
+    exit_code: 0,
+    mm: UserAddressSpace::empty(),
+    files: FileTable::empty(),
 };
 
-struct SchedulerInner<const C: usize> {
+/// Contexts cores run before they start scheduling, each pinned to its core with pid 0.
+/// Only the boot core one ever blocks, while `kernel_main` waits for its first process, and
+/// run queues are searched in core order, so waking pid 0 finds it.
+static mut IDLE_TASKS: [Task; NUM_CORES] = [IDLE_TASK; NUM_CORES];
+
+struct RunQueue<const C: usize> {
     tasks: heapless::Vec<WrappedPointer<Task>, C>,
     current: usize,
 }
 
+/// Each core schedules tasks from its own run queue. Queues are locked one at a time, except
+/// for migrations, which lock both in core order.
 pub struct Scheduler<const C: usize> {
-    inner: IRQSafeNullLock<SchedulerInner<C>>,
+    queues: [IRQSpinLock<RunQueue<C>>; NUM_CORES],
+    /// Bit per core which has its idle task registered
+    online: AtomicU64,
+    ticks: AtomicU64,
+}
+
+/// Only stable with IRQs masked, otherwise current task may move to another core
+fn core_id() -> usize {
+    unsafe { core_id_el1() as usize }
 }
 
-impl<const C: usize> SchedulerInner<C> {
+impl<const C: usize> RunQueue<C> {
     const fn new() -> Self {
         Self {
             tasks: heapless::Vec::new(),
@@ -58,48 +114,116 @@ impl<const C: usize> SchedulerInner<C> {
         }
     }
 
-    pub fn init(&mut self) {
-        unsafe {
-            self.tasks
-                .push(WrappedPointer::new(&mut INIT_TASK as *mut Task as usize))
-                .expect("push init task")
-        };
-    }
-
     fn push_task(&mut self, task: WrappedPointer<Task>) {
         if self.tasks.push(task).is_err() {
             panic!("task cache is full")
         }
     }
 
+    fn remove(&mut self, idx: usize) -> WrappedPointer<Task> {
+        let task = self.tasks.swap_remove(idx);
+        if self.current == self.tasks.len() {
+            self.current = idx;
+        }
+
+        task
+    }
+
     fn current(&mut self) -> Option<&mut WrappedPointer<Task>> {
         self.tasks.get_mut(self.current)
     }
 
-    fn schedule(&mut self) {
-        self.preempt_disable();
+    fn find(&self, pid: u64) -> Option<usize> {
+        self.tasks.iter().position(|task| task.pid == pid)
+    }
+
+    /// Runnable tasks other than the idle one
+    fn load(&self) -> usize {
+        self.tasks
+            .iter()
+            .filter(|task| task.pid != 0 && task.state == TaskState::Running)
+            .count()
+    }
+
+    /// Returns `true` if the task was waiting
+    fn wake(&mut self, idx: usize) -> bool {
+        let task = &mut self.tasks[idx];
+        if matches!(task.state, TaskState::Blocked | TaskState::Sleeping) {
+            task.state = TaskState::Running;
+            return true;
+        }
 
-        let next = loop {
+        false
+    }
+
+    /// Task stops running once the run queue lock is released and `reschedule` called
+    fn block_current(&mut self, state: TaskState) {
+        if let Some(current) = self.current() {
+            current.state = state;
+            current.counter = 0;
+        }
+    }
+
+    fn exit_current(&mut self, code: i64) {
+        if let Some(current) = self.current() {
+            current.exit_code = code;
+            current.state = TaskState::Zombie;
+        }
+    }
+
+    /// Zombie can be freed only once its core switched away from it
+    fn is_reapable(&self, idx: usize) -> bool {
+        let task = &self.tasks[idx];
+        task.state == TaskState::Zombie && !task.on_cpu
+    }
+
+    fn reap(&mut self, idx: usize) -> Option<(WrappedPointer<Task>, i64)> {
+        if !self.is_reapable(idx) {
+            return None;
+        }
+
+        let task = self.remove(idx);
+        let code = task.exit_code;
+
+        Some((task, code))
+    }
+
+    /// Runnable task which isn't on any CPU and may run on `dst`
+    fn find_migratable<F>(&self, dst: usize, select: F) -> Option<usize>
+    where
+        F: Fn(&Task) -> bool,
+    {
+        self.tasks.iter().position(|task| {
+            task.state == TaskState::Running
+                && !task.on_cpu
+                && task.affinity & (1 << dst) != 0
+                && select(task)
+        })
+    }
+
+    /// Runnable task with the most time left, `None` when every task allowed on `core_id` is
+    /// blocked
+    fn pick_next(&mut self, core_id: usize) -> Option<usize> {
+        loop {
             let max = self
                 .tasks
                 .iter()
                 .enumerate()
-                .filter(|(_, def)| def.state == TaskState::Running)
+                .filter(|(_, def)| {
+                    def.state == TaskState::Running && def.affinity & (1 << core_id) != 0
+                })
                 .max_by(|(_, t1), (_, t2)| t1.counter.cmp(&t2.counter));
 
             match max {
-                Some((idx, ptr)) if ptr.counter > 0 => break idx,
-                _ => {
+                Some((idx, ptr)) if ptr.counter > 0 => return Some(idx),
+                Some(_) => {
                     for task in self.tasks.iter_mut() {
                         task.counter = task.priority
                     }
                 }
+                None => return None,
             }
-        };
-
-        self.switch_to(next);
-
-        self.preempt_enable();
+        }
     }
 
     fn preempt_disable(&mut self) {
@@ -114,86 +238,559 @@ impl<const C: usize> SchedulerInner<C> {
         }
     }
 
-    fn switch_to(&mut self, next: usize) {
+    /// Makes `next` current and returns tasks `cpu_switch_to` has to switch between, `None`
+    /// if `next` is current already
+    fn switch_to(&mut self, next: usize) -> Option<(*const Task, *const Task)> {
         if self.current == next {
-            return;
+            return None;
         }
 
         crate::trace!("Switching to task id {}", next);
 
-        let last = self.tasks.get(self.current).expect("last");
+        let last = self.tasks.get(self.current).expect("last").addr() as *const Task;
         self.current = next;
-        let next = self.tasks.get(next).expect("next");
+        let next = self.tasks.get_mut(next).expect("next");
+        next.on_cpu = true;
 
-        cpu_switch_to(last, next);
+        Some((last, next.addr() as *const Task))
+    }
+
+    /// Clears `on_cpu` of the task switched away from, returns its pid, state and affinity
+    fn finish_switch(&mut self) -> Option<(u64, TaskState, u64)> {
+        let current = self.current;
+        let mut prev = None;
+        for (idx, task) in self.tasks.iter_mut().enumerate() {
+            if idx != current && task.on_cpu {
+                task.on_cpu = false;
+                prev = Some((task.pid, task.state, task.affinity));
+            }
+        }
+
+        prev
     }
 }
 
 impl<const C: usize> Scheduler<C> {
+    const EMPTY_QUEUE: IRQSpinLock<RunQueue<C>> = IRQSpinLock::new(RunQueue::new());
+
     pub const fn new() -> Self {
         Self {
-            inner: IRQSafeNullLock::new(SchedulerInner::new()),
+            queues: [Self::EMPTY_QUEUE; NUM_CORES],
+            online: AtomicU64::new(0),
+            ticks: AtomicU64::new(0),
         }
     }
 
+    /// Makes context running on the calling core its idle task and starts placing tasks on
+    /// the core
     pub fn init(&self) {
-        self.inner.map_locked(|inner| inner.init());
+        self.map_local(|queue| {
+            let core_id = core_id();
+            let mut idle: WrappedPointer<Task> =
+                unsafe { WrappedPointer::new(&mut IDLE_TASKS[core_id] as *mut Task as usize) };
+            idle.affinity = 1 << core_id;
+            queue.push_task(idle);
+
+            self.online.fetch_or(1 << core_id, Ordering::Release);
+        })
     }
 
+    /// Queues new task on the least busy core it may run on
     pub fn register_new_waiting_task(&self, task: WrappedPointer<Task>) {
-        self.inner.map_locked(|inner| inner.push_task(task))
+        let core_id = self
+            .least_busy(task.affinity)
+            .expect("no online core for new task");
+        self.queues[core_id].map_locked(|queue| queue.push_task(task));
+        self.kick(core_id);
+    }
+
+    /// Runs `f` on run queue of the calling core
+    fn map_local<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut RunQueue<C>) -> R,
+    {
+        let state = local_irq_save();
+        mask_irq();
+        let res = self.queues[core_id()].map_locked(f);
+        local_irq_restore(state);
+
+        res
+    }
+
+    /// Runs `f` on run queue holding task `pid` and its index there, returns the queue core
+    /// too. Only runnable tasks migrate, so blocked and exited ones are found.
+    fn map_task<F, R>(&self, pid: u64, mut f: F) -> Option<(usize, R)>
+    where
+        F: FnMut(&mut RunQueue<C>, usize) -> R,
+    {
+        self.queues.iter().enumerate().find_map(|(core_id, queue)| {
+            queue
+                .map_locked(|queue| queue.find(pid).map(|idx| f(queue, idx)))
+                .map(|res| (core_id, res))
+        })
+    }
+
+    /// Locks run queues of cores `a` and `b`, lower core first
+    fn map_pair<F, R>(&self, a: usize, b: usize, f: F) -> R
+    where
+        F: FnOnce(&mut RunQueue<C>, &mut RunQueue<C>) -> R,
+    {
+        let (first, second) = if a < b { (a, b) } else { (b, a) };
+        self.queues[first].map_locked(|first_queue| {
+            self.queues[second].map_locked(|second_queue| {
+                if a < b {
+                    f(first_queue, second_queue)
+                } else {
+                    f(second_queue, first_queue)
+                }
+            })
+        })
+    }
+
+    fn online_cores(&self) -> impl Iterator<Item = usize> {
+        let online = self.online.load(Ordering::Acquire);
+        (0..NUM_CORES).filter(move |core_id| online & (1 << core_id) != 0)
+    }
+
+    fn least_busy(&self, affinity: u64) -> Option<usize> {
+        self.online_cores()
+            .filter(|core_id| affinity & (1 << core_id) != 0)
+            .min_by_key(|&core_id| self.queues[core_id].map_locked(|queue| queue.load()))
+    }
+
+    /// Makes core `core_id` reschedule soon, unless it's the calling one
+    fn kick(&self, core_id: usize) {
+        if core_id != core_id() {
+            INTERRUPT_CONTROLLER.send_ipi(core_id)
+        }
     }
 
     fn preempt_enable(&self) {
-        self.inner.map_locked(|inner| inner.preempt_enable());
+        self.map_local(|queue| queue.preempt_enable());
+    }
+
+    /// Switches to the next runnable task and returns once current one gets picked again.
+    /// Has to be called with IRQs masked and no locks held, otherwise the next task could
+    /// interrupt the switch or spin on a lock which is never released.
+    fn reschedule(&self) {
+        assert_no_locks_held();
+        self.preempt_disable();
+
+        loop {
+            let core_id = core_id();
+            let picked = self.queues[core_id]
+                .map_locked(|queue| queue.pick_next(core_id).map(|next| queue.switch_to(next)));
+
+            match picked {
+                Some(Some((last, next))) => {
+                    unsafe { Task::cpu_switch_to(&*last, &*next) };
+                    self.finish_switch();
+                    break;
+                }
+                Some(None) => break,
+                // Every task is blocked, wait for an interrupt to wake some of them
+                None => unsafe {
+                    unmask_irq();
+                    wfi();
+                    mask_irq();
+                },
+            }
+        }
+
+        self.preempt_enable();
+    }
+
+    /// Runs on the task switched to, once the previous one's context is saved. Tasks waiting
+    /// for an exit are woken only now, as the zombie can't be freed any earlier. Previous task
+    /// leaves right away if its affinity no longer allows this core.
+    fn finish_switch(&self) {
+        let core_id = core_id();
+        match self.queues[core_id].map_locked(|queue| queue.finish_switch()) {
+            Some((_, TaskState::Zombie, _)) => TASK_EXIT_QUEUE.wake_all(),
+            Some((pid, TaskState::Running, affinity)) if affinity & (1 << core_id) == 0 => {
+                self.move_task(core_id, pid, affinity)
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs `f` under the run queue lock and reschedules once it's released if `f` returns
+    /// `true`. IRQs stay masked in between, so no tick can reschedule in the meantime.
+    fn update_and_reschedule<F>(&self, f: F)
+    where
+        F: FnOnce(&mut RunQueue<C>) -> bool,
+    {
+        let state = local_irq_save();
+        mask_irq();
+        if self.map_local(f) {
+            self.reschedule();
+        }
+        local_irq_restore(state);
+    }
+
+    pub fn current_pid(&self) -> u64 {
+        self.map_local(|queue| queue.current().map(|task| task.pid))
+            .expect("current task")
+    }
+
+    /// Kernel stack of current task, `None` for idle tasks running on core stacks.
+    /// Used while panicking, so it gives up instead of waiting for the run queue lock.
+    pub fn current_stack(&self) -> Option<Range<usize>> {
+        self.queues[core_id()]
+            .try_map_locked(|queue| {
+                let task = queue.current()?;
+                if task.pid == 0 {
+                    return None;
+                }
+
+                Some(task.addr() + size_of::<Task>()..task.addr() + KernelGranule::SIZE)
+            })
+            .flatten()
+    }
+
+    /// Runs `f` on current task with IRQs masked
+    pub fn map_current<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Task) -> R,
+    {
+        self.map_local(|queue| f(queue.current().expect("current task")))
+    }
+
+    /// Gives up the rest of current time slice
+    pub fn yield_now(&self) {
+        self.update_and_reschedule(|queue| {
+            if let Some(current) = queue.current() {
+                current.counter = 0;
+            }
+
+            true
+        })
+    }
+
+    /// Idle loop of the calling core, entered once it has nothing else to do
+    pub fn idle(&self) -> ! {
+        loop {
+            self.yield_now();
+            unsafe { wfi() };
+        }
+    }
+
+    /// Blocks current task in `state` unless `register` returns `false`. `register` receives
+    /// current task pid and runs with IRQs masked but no run queue locked, after the state is
+    /// set. A wake up racing with it makes the task runnable again instead of getting lost.
+    pub fn block_current<F>(&self, state: TaskState, register: F)
+    where
+        F: FnOnce(u64) -> bool,
+    {
+        let irq_state = local_irq_save();
+        mask_irq();
+
+        let pid = self.map_local(|queue| {
+            queue.block_current(state);
+            queue.current().expect("current task").pid
+        });
+        if register(pid) {
+            self.reschedule();
+        } else {
+            self.map_local(|queue| {
+                queue.current().expect("current task").state = TaskState::Running
+            });
+        }
+
+        local_irq_restore(irq_state);
+    }
+
+    /// Makes blocked or sleeping task runnable again, safe to call from IRQ handlers. Task
+    /// whose affinity changed while it was waiting moves to a core it may run on.
+    pub fn wake(&self, pid: u64) {
+        let woken = self.map_task(pid, |queue, idx| {
+            (queue.wake(idx), queue.tasks[idx].affinity)
+        });
+        if let Some((core_id, (true, affinity))) = woken {
+            if affinity & (1 << core_id) == 0 {
+                self.move_task(core_id, pid, affinity)
+            } else {
+                self.kick(core_id)
+            }
+        }
+    }
+
+    fn exit_current(&self, code: i64) -> ! {
+        self.update_and_reschedule(|queue| {
+            queue.exit_current(code);
+            true
+        });
+
+        unreachable!("zombie task was scheduled")
+    }
+
+    /// Child of `parent` is checked and reaped under one run queue lock, so the pid can't
+    /// change hands in between
+    fn reap(
+        &self,
+        pid: u64,
+        parent: u64,
+    ) -> Result<Option<(WrappedPointer<Task>, i64)>, &'static str> {
+        if pid == parent {
+            return Err("task cannot wait for itself");
+        }
+
+        self.map_task(pid, |queue, idx| {
+            if queue.tasks[idx].parent != parent {
+                return Err("task is not a child of the caller");
+            }
+
+            Ok(queue.reap(idx))
+        })
+        .map(|(_, reaped)| reaped)
+        .ok_or("no task with given pid")?
+    }
+
+    /// Child `pid` of `parent` can be reaped, or `reap` would fail
+    fn exited(&self, pid: u64, parent: u64) -> bool {
+        self.map_task(pid, |queue, idx| {
+            queue.tasks[idx].parent != parent || queue.is_reapable(idx)
+        })
+        .map_or(true, |(_, exited)| exited)
+    }
+
+    /// Blocks until child task with `pid` exits, releases its resources and returns its exit
+    /// code. Only the task which spawned it may wait for it.
+    pub fn wait(&self, pid: u64) -> Result<i64, &'static str> {
+        let parent = self.current_pid();
+        loop {
+            if let Some((mut task, code)) = self.reap(pid, parent)? {
+                task.mm.release();
+                task.files.close_all();
+                free_page(Address::new(task.addr()))?;
+                crate::trace!("reaped task {}", pid);
+                return Ok(code);
+            }
+
+            TASK_EXIT_QUEUE.wait_until(|| self.exited(pid, parent));
+        }
+    }
+
+    /// Restricts task `pid` to cores in `affinity`. Runnable task on another core moves right
+    /// away, running one once its core switches away from it and waiting one once it's woken.
+    pub fn set_affinity(&self, pid: u64, affinity: u64) -> Result<(), &'static str> {
+        if pid == 0 {
+            return Err("idle tasks are pinned to their cores");
+        }
+        if self
+            .online_cores()
+            .all(|core_id| affinity & (1 << core_id) == 0)
+        {
+            return Err("no online core in affinity mask");
+        }
+
+        let (core_id, on_cpu) = self
+            .map_task(pid, |queue, idx| {
+                let task = &mut queue.tasks[idx];
+                task.affinity = affinity;
+                task.on_cpu
+            })
+            .ok_or("no task with given pid")?;
+        if affinity & (1 << core_id) != 0 {
+            return Ok(());
+        }
+
+        if on_cpu {
+            self.kick(core_id)
+        } else {
+            self.move_task(core_id, pid, affinity)
+        }
+
+        Ok(())
+    }
+
+    /// Moves runnable task `pid` from core `src` to the least busy core `affinity` allows
+    fn move_task(&self, src: usize, pid: u64, affinity: u64) {
+        if let Some(dst) = self.least_busy(affinity & !(1 << src)) {
+            self.migrate(src, dst, |task| task.pid == pid);
+        }
+    }
+
+    /// Moves first task `select` accepts from core `src` to `dst`, `false` if none could move
+    fn migrate<F>(&self, src: usize, dst: usize, select: F) -> bool
+    where
+        F: Fn(&Task) -> bool,
+    {
+        let moved = self.map_pair(src, dst, |src_queue, dst_queue| {
+            if dst_queue.tasks.len() == C {
+                return false;
+            }
+
+            match src_queue.find_migratable(dst, &select) {
+                Some(idx) => {
+                    dst_queue.push_task(src_queue.remove(idx));
+                    true
+                }
+                None => false,
+            }
+        });
+        if moved {
+            self.kick(dst);
+        }
+
+        moved
+    }
+
+    /// Moves tasks off cores their affinity excludes, then a task from the busiest core to the
+    /// least busy one if that evens their load out
+    fn balance(&self) {
+        for src in self.online_cores() {
+            let misplaced = |task: &Task| task.affinity & (1 << src) == 0;
+            while let Some(affinity) = self.queues[src].map_locked(|queue| {
+                queue
+                    .tasks
+                    .iter()
+                    .find(|task| {
+                        task.state == TaskState::Running && !task.on_cpu && misplaced(task)
+                    })
+                    .map(|task| task.affinity)
+            }) {
+                match self.least_busy(affinity) {
+                    Some(dst) if self.migrate(src, dst, misplaced) => {}
+                    _ => break,
+                }
+            }
+        }
+
+        let load = |core_id: usize| self.queues[core_id].map_locked(|queue| queue.load());
+        let busiest = self.online_cores().max_by_key(|&core_id| load(core_id));
+        let least_busy = self.online_cores().min_by_key(|&core_id| load(core_id));
+        if let (Some(src), Some(dst)) = (busiest, least_busy) {
+            if load(src) >= load(dst) + 2 {
+                self.migrate(src, dst, |_| true);
+            }
+        }
     }
 
     fn preempt_disable(&self) {
-        self.inner.map_locked(|inner| inner.preempt_disable());
+        self.map_local(|queue| queue.preempt_disable());
+    }
+
+    pub fn print_status(&self) {
+        crate::info!("tasks:");
+        for core_id in self.online_cores() {
+            crate::info!("  core {}:", core_id);
+            self.queues[core_id].map_locked(|queue| {
+                for (idx, task) in queue.tasks.iter().enumerate() {
+                    let mode = if task.mm.regions().next().is_some() {
+                        "user"
+                    } else {
+                        "kernel"
+                    };
+
+                    crate::info!(
+                        "    {} pid {:>3}: {:?}, {}, priority {}, counter {}, affinity {:#x}",
+                        if idx == queue.current { '*' } else { ' ' },
+                        task.pid,
+                        task.state,
+                        mode,
+                        task.priority,
+                        task.counter,
+                        task.affinity
+                    );
+                }
+            })
+        }
     }
 
+    /// Counts down current task time slice. Task which may no longer run on this core is
+    /// switched away from right away.
     pub(crate) fn schedule(&self) {
-        self.inner.map_locked(|inner| {
-            let current = if let Some(current) = inner.current() {
+        self.update_and_reschedule(|queue| {
+            let core_id = core_id();
+            let current = if let Some(current) = queue.current() {
                 current
             } else {
-                return;
+                return false;
             };
             current.counter = current.counter.saturating_sub(1);
-            if current.counter > 0 || current.preempt_count > 0 {
-                return;
+            if current.preempt_count > 0 {
+                return false;
+            }
+            if current.counter > 0 && current.affinity & (1 << core_id) != 0 {
+                return false;
             }
             current.counter = 0;
-            unmask_irq();
-            inner.schedule();
-            mask_irq();
+
+            true
         })
     }
 }
 
+/// Only the boot core gets timer ticks, it forwards them to other cores with IPIs
 impl<const C: usize> TickCallbackHandler for Scheduler<C> {
     fn handle(&self) {
+        let core_id = core_id();
+        for other in self.online_cores().filter(|&other| other != core_id) {
+            INTERRUPT_CONTROLLER.send_ipi(other)
+        }
+
+        if self.ticks.fetch_add(1, Ordering::Relaxed) % BALANCE_INTERVAL == 0 {
+            self.balance();
+        }
+
         self.schedule()
     }
 }
 
-fn cpu_switch_to(last: &Task, new: &Task) {
-    unsafe { Task::cpu_switch_to(last, new) }
+/// IPIs are forwarded ticks or kicks from other cores, the latter cut time slice short by a tick
+impl<const C: usize> IRQHandler for Scheduler<C> {
+    fn handle(&self) -> Result<(), &'static str> {
+        self.schedule();
+
+        Ok(())
+    }
 }
 
-pub unsafe fn spawn_process(f: u64, arg: u64) -> Result<(), &'static str> {
-    SCHEDULER.preempt_disable();
+/// Spawns kernel process running `f`, which has to be an `extern "C" fn(u64) -> i64`.
+/// Value returned by `f` becomes task exit code.
+pub unsafe fn spawn_process(f: u64, arg: u64) -> Result<u64, &'static str> {
+    spawn(f, arg, |_, _| {})
+}
+
+/// Spawns process running in EL0 from `entry` on `stack`, both have to be mapped with EL0
+/// permissions in `mm`, which becomes the task's address space. `files` become its open files.
+pub unsafe fn spawn_user_process(
+    entry: Address<Virtual>,
+    stack: Address<Virtual>,
+    mm: UserAddressSpace,
+    files: FileTable,
+) -> Result<u64, &'static str> {
+    spawn(0, 0, |task, regs| {
+        task.mm = mm;
+        task.files = files;
+        regs.move_to_user_mode(entry.addr() as u64, stack.addr() as u64)
+    })
+}
+
+/// `return_from_fork` calls `f` when it's non zero, otherwise it erets using `PtRegs`
+unsafe fn spawn<F>(f: u64, arg: u64, setup_regs: F) -> Result<u64, &'static str>
+where
+    F: FnOnce(&mut Task, &mut PtRegs),
+{
     let page = next_free_page()?;
+    let parent = SCHEDULER.current_pid();
+    SCHEDULER.preempt_disable();
     let mut task: WrappedPointer<Task> = WrappedPointer::new(page.addr());
 
-    let child_regs = pt_regs(&task);
+    let mut child_regs = pt_regs(&task);
     (child_regs.addr() as *mut PtRegs).write_bytes(0, 1);
-    (task.addr() as *mut CpuContext).write_bytes(0, 1); // This works on behalf of CpuContext being first field
+    (task.addr() as *mut Task).write_bytes(0, 1);
+    setup_regs(&mut task, &mut child_regs);
 
+    let pid = NEXT_PID.fetch_add(1, Ordering::Relaxed);
+    task.pid = pid;
+    task.parent = parent;
     task.context.x19 = f;
     task.context.x20 = arg;
 
     task.priority = 10;
+    task.affinity = ALL_CORES;
     task.state = TaskState::Running;
     task.counter = task.priority;
     task.preempt_count = 1;
@@ -204,14 +801,28 @@ pub unsafe fn spawn_process(f: u64, arg: u64) -> Result<(), &'static str> {
     SCHEDULER.register_new_waiting_task(task);
     SCHEDULER.preempt_enable();
 
-    Ok(())
+    Ok(pid)
 }
 
 unsafe fn pt_regs(task: &WrappedPointer<Task>) -> WrappedPointer<PtRegs> {
-    WrappedPointer::new(task.addr() + 4096 - size_of::<PtRegs>())
+    WrappedPointer::new(task.addr() + KernelGranule::SIZE - size_of::<PtRegs>())
 }
 
+/// New tasks start here with IRQs masked, as tasks are switched with them masked
 #[no_mangle]
 fn schedule_tail() {
-    SCHEDULER.preempt_enable()
+    SCHEDULER.finish_switch();
+    SCHEDULER.preempt_enable();
+    unmask_irq();
+}
+
+#[no_mangle]
+pub extern "C" fn task_exit(code: i64) -> ! {
+    crate::trace!("task {} exited with code {}", SCHEDULER.current_pid(), code);
+
+    // Closing files can reach driver code, so it's done after the run queue lock is released
+    let mut files = SCHEDULER.map_current(|task| mem::take(&mut task.files));
+    files.close_all();
+
+    SCHEDULER.exit_current(code)
 }
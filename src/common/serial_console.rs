@@ -1,4 +1,4 @@
-use core::fmt;
+use core::{fmt, str::FromStr};
 
 pub trait Write {
     fn write_char(&self, c: char);
@@ -10,3 +10,128 @@ pub trait Read {
     fn read_char(&self) -> char;
     fn clear(&self);
 }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// Serial line settings, parsed from and displayed as e.g. `115200 8N1` or `921600 8E2 crtscts`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineConfig {
+    pub baud: u32,
+    /// Data bits in a frame, 5 to 8
+    pub word_length: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// CTS/RTS hardware flow control
+    pub flow_control: bool,
+}
+
+pub trait Configure {
+    fn line_config(&self) -> LineConfig;
+    fn set_line_config(&self, config: LineConfig) -> Result<(), &'static str>;
+}
+
+impl LineConfig {
+    pub const fn new() -> Self {
+        Self {
+            baud: 115200,
+            word_length: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: false,
+        }
+    }
+
+    /// Parses frame format such as `8N1`
+    fn parse_frame(&mut self, frame: &str) -> Result<(), &'static str> {
+        let frame = frame.as_bytes();
+        if frame.len() != 3 {
+            return Err("frame format has to look like 8N1");
+        }
+
+        self.word_length = match frame[0] {
+            b'5'..=b'8' => frame[0] - b'0',
+            _ => return Err("word length has to be between 5 and 8"),
+        };
+        self.parity = match frame[1].to_ascii_uppercase() {
+            b'N' => Parity::None,
+            b'E' => Parity::Even,
+            b'O' => Parity::Odd,
+            _ => return Err("parity has to be N, E or O"),
+        };
+        self.stop_bits = match frame[2] {
+            b'1' => StopBits::One,
+            b'2' => StopBits::Two,
+            _ => return Err("stop bits have to be 1 or 2"),
+        };
+
+        Ok(())
+    }
+}
+
+impl Default for LineConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromStr for LineConfig {
+    type Err = &'static str;
+
+    /// Accepts `<baud> [<frame>] [crtscts|-crtscts]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::new();
+        let mut words = s.split_whitespace();
+
+        config.baud = words
+            .next()
+            .ok_or("missing baud rate")?
+            .parse()
+            .map_err(|_| "baud rate is not a number")?;
+
+        for word in words {
+            match word {
+                "crtscts" => config.flow_control = true,
+                "-crtscts" => config.flow_control = false,
+                frame => config.parse_frame(frame)?,
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl fmt::Display for LineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+
+        write!(
+            f,
+            "{} {}{}{}",
+            self.baud, self.word_length, parity, stop_bits
+        )?;
+        if self.flow_control {
+            write!(f, " crtscts")?;
+        }
+
+        Ok(())
+    }
+}
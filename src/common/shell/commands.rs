@@ -0,0 +1,328 @@
+use alloc::vec::Vec;
+use core::{convert::TryFrom, ptr, str::FromStr};
+
+use crate::{
+    common::{
+        dmesg,
+        elf::loader::spawn_elf,
+        fs::{self, devfs, AccessMode},
+        memory::heap::KERNEL_HEAP,
+        scheduler::SCHEDULER,
+        serial_console::{Configure, LineConfig, Write},
+        statics::{
+            BSP_DRIVER_MANAGER,
+            CLOCK_TIMER,
+            CONSOLE,
+            FRAME_ALLOCATOR,
+            INTERRUPT_CONTROLLER,
+            KERNEL_MAPPING_RECORD,
+            POWER_MANAGEMENT,
+        },
+        sync::{Mutex, ReadWriteLock},
+        time::clock::ClockManager,
+        tty::{TtyMode, TTY},
+    },
+    log::{print_log_filters, set_log_level, set_module_log_level},
+    println,
+};
+
+type CommandResult = Result<(), &'static str>;
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    run: fn(&[&str]) -> CommandResult,
+}
+
+static COMMANDS: [Command; 16] = [
+    Command {
+        name: "help",
+        usage: "help",
+        help: "list commands",
+        run: help,
+    },
+    Command {
+        name: "ps",
+        usage: "ps",
+        help: "list scheduler tasks",
+        run: ps,
+    },
+    Command {
+        name: "taskset",
+        usage: "taskset <pid> <mask>",
+        help: "restrict task to cores set in mask",
+        run: taskset,
+    },
+    Command {
+        name: "mem",
+        usage: "mem",
+        help: "show kernel mappings, frame allocator and heap",
+        run: mem,
+    },
+    Command {
+        name: "drivers",
+        usage: "drivers",
+        help: "list drivers and device nodes",
+        run: drivers,
+    },
+    Command {
+        name: "irq",
+        usage: "irq",
+        help: "show registered interrupt handlers",
+        run: irq,
+    },
+    Command {
+        name: "dmesg",
+        usage: "dmesg",
+        help: "print kernel log kept in memory",
+        run: dmesg,
+    },
+    Command {
+        name: "uptime",
+        usage: "uptime",
+        help: "time since boot",
+        run: uptime,
+    },
+    Command {
+        name: "loglevel",
+        usage: "loglevel [module] [0-4|off]",
+        help: "show or set global or per module log level, 0 is errors only, 4 is trace",
+        run: loglevel,
+    },
+    Command {
+        name: "peek",
+        usage: "peek <addr>",
+        help: "read u32 from kernel address, unmapped ones panic",
+        run: peek,
+    },
+    Command {
+        name: "poke",
+        usage: "poke <addr> <value>",
+        help: "write u32 to kernel address, unmapped ones panic",
+        run: poke,
+    },
+    Command {
+        name: "stty",
+        usage: "stty [<baud> [8N1] [crtscts]]",
+        help: "show or set console line settings",
+        run: stty,
+    },
+    Command {
+        name: "ls",
+        usage: "ls [path]",
+        help: "list directory",
+        run: ls,
+    },
+    Command {
+        name: "cat",
+        usage: "cat <path>",
+        help: "print file",
+        run: cat,
+    },
+    Command {
+        name: "run",
+        usage: "run <path> [args]",
+        help: "start ELF program and wait for it",
+        run: run_program,
+    },
+    Command {
+        name: "reboot",
+        usage: "reboot",
+        help: "reset the board with the watchdog",
+        run: reboot,
+    },
+];
+
+pub fn run(name: &str, args: &[&str]) {
+    match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => {
+            if let Err(e) = (command.run)(args) {
+                println!("{}: {}", name, e);
+            }
+        }
+        None => println!("unknown command `{}`, try `help`", name),
+    }
+}
+
+/// Accepts decimal and `0x` prefixed hex numbers
+fn parse_number(s: &str) -> Result<u64, &'static str> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| "invalid number")
+}
+
+fn parse_address(s: &str) -> Result<*mut u32, &'static str> {
+    let addr = parse_number(s)? as usize;
+    if addr % 4 != 0 {
+        return Err("address has to be 4 byte aligned");
+    }
+
+    Ok(addr as *mut u32)
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, &'static str> {
+    let file = fs::open(path, AccessMode::ReadOnly).map_err(|_| "can't open file")?;
+    let mut data = Vec::new();
+    let mut chunk = [0; 512];
+
+    loop {
+        match file.read(&mut chunk).map_err(|_| "can't read file")? {
+            0 => return Ok(data),
+            read => data.extend_from_slice(&chunk[..read]),
+        }
+    }
+}
+
+fn help(_args: &[&str]) -> CommandResult {
+    for command in COMMANDS.iter() {
+        println!("  {:<30} {}", command.usage, command.help);
+    }
+
+    Ok(())
+}
+
+fn ps(_args: &[&str]) -> CommandResult {
+    SCHEDULER.print_status();
+
+    Ok(())
+}
+
+fn taskset(args: &[&str]) -> CommandResult {
+    let pid = parse_number(args.first().ok_or("missing pid")?)?;
+    let mask = parse_number(args.get(1).ok_or("missing mask")?)?;
+
+    SCHEDULER.set_affinity(pid, mask)
+}
+
+fn mem(_args: &[&str]) -> CommandResult {
+    KERNEL_MAPPING_RECORD.map_read(|r| r.print_status());
+    FRAME_ALLOCATOR.map_locked(|a| a.stats()).print_status();
+    KERNEL_HEAP.print_status();
+
+    Ok(())
+}
+
+fn drivers(_args: &[&str]) -> CommandResult {
+    BSP_DRIVER_MANAGER.print_status();
+    devfs::print_status();
+
+    Ok(())
+}
+
+fn irq(_args: &[&str]) -> CommandResult {
+    INTERRUPT_CONTROLLER.print_status();
+
+    Ok(())
+}
+
+fn dmesg(_args: &[&str]) -> CommandResult {
+    let mut chunk = [0; 256];
+    let mut offset = 0;
+
+    loop {
+        match dmesg::read_at(offset, &mut chunk) {
+            0 => return Ok(()),
+            read => {
+                TTY.write(&chunk[..read]);
+                offset += read;
+            }
+        }
+    }
+}
+
+fn uptime(_args: &[&str]) -> CommandResult {
+    let uptime = CLOCK_TIMER.map_locked(|t| t.uptime());
+    println!("{}.{:06}s", uptime.as_secs(), uptime.subsec_micros());
+
+    Ok(())
+}
+
+fn parse_log_level(s: &str) -> Result<usize, &'static str> {
+    usize::from_str(s).map_err(|_| "invalid log level")
+}
+
+fn loglevel(args: &[&str]) -> CommandResult {
+    match args {
+        [] => {
+            print_log_filters();
+            Ok(())
+        }
+        [level] => set_log_level(parse_log_level(level)?),
+        [module, "off"] => set_module_log_level(module, None),
+        [module, level] => set_module_log_level(module, Some(parse_log_level(level)?)),
+        _ => Err("too many arguments"),
+    }
+}
+
+fn peek(args: &[&str]) -> CommandResult {
+    let addr = parse_address(args.first().ok_or("missing address")?)?;
+    let value = unsafe { ptr::read_volatile(addr) };
+    println!("{:p}: {:#010x}", addr, value);
+
+    Ok(())
+}
+
+fn poke(args: &[&str]) -> CommandResult {
+    let addr = parse_address(args.first().ok_or("missing address")?)?;
+    let value = parse_number(args.get(1).ok_or("missing value")?)?;
+    let value = u32::try_from(value).map_err(|_| "value doesn't fit in u32")?;
+    unsafe { ptr::write_volatile(addr, value) };
+
+    Ok(())
+}
+
+fn stty(args: &[&str]) -> CommandResult {
+    if args.is_empty() {
+        println!("{}", CONSOLE.line_config());
+        return Ok(());
+    }
+
+    let config = LineConfig::from_str(&args.join(" "))?;
+    CONSOLE.set_line_config(config)
+}
+
+fn ls(args: &[&str]) -> CommandResult {
+    let path = args.first().copied().unwrap_or("/");
+    let names = fs::lookup(path)
+        .and_then(|inode| inode.list())
+        .map_err(|_| "not a directory")?;
+
+    for name in names {
+        println!("{}", name);
+    }
+
+    Ok(())
+}
+
+fn cat(args: &[&str]) -> CommandResult {
+    let data = read_file(args.first().ok_or("missing path")?)?;
+    TTY.write(&data);
+
+    Ok(())
+}
+
+/// Program owns the console until it exits, so it gets the tty in canonical mode
+fn run_program(args: &[&str]) -> CommandResult {
+    let path = args.first().ok_or("missing path")?;
+    let image = read_file(path)?;
+
+    TTY.set_mode(TtyMode::Canonical);
+    let result = spawn_elf(&image, args, &[]).and_then(|pid| {
+        let code = SCHEDULER.wait(pid)?;
+        println!("{} exited with code {}", path, code);
+        Ok(())
+    });
+    TTY.set_mode(TtyMode::Raw);
+
+    result
+}
+
+fn reboot(_args: &[&str]) -> CommandResult {
+    println!("rebooting");
+    CONSOLE.flush();
+
+    POWER_MANAGEMENT.reset()
+}
@@ -0,0 +1,191 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    common::tty::{TtyMode, TTY},
+    print,
+    println,
+};
+
+mod commands;
+
+const PROMPT: &str = "dotos> ";
+const MAX_LINE: usize = 256;
+const HISTORY_SIZE: usize = 32;
+
+const CTRL_C: u8 = 0x03;
+const BACKSPACE: u8 = 0x08;
+const ESC: u8 = 0x1b;
+const DELETE: u8 = 0x7f;
+
+enum Key {
+    Char(u8),
+    Enter,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Interrupt,
+    Ignored,
+}
+
+/// Reads lines from the tty in raw mode, so it can handle cursor keys and history itself
+struct LineEditor {
+    line: Vec<u8>,
+    /// Index into `line`
+    cursor: usize,
+    history: Vec<String>,
+    /// Entry shown while browsing history, `history.len()` stands for the edited line
+    history_idx: usize,
+    /// Terminals may send `\r\n` on enter, the `\n` must not submit an empty line
+    after_cr: bool,
+}
+
+fn read_byte() -> u8 {
+    let mut byte = [0];
+    while TTY.read(&mut byte) == 0 {}
+
+    byte[0]
+}
+
+impl LineEditor {
+    const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_idx: 0,
+            after_cr: false,
+        }
+    }
+
+    fn read_key(&mut self) -> Key {
+        let byte = read_byte();
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+
+        match byte {
+            b'\n' if after_cr => Key::Ignored,
+            b'\r' | b'\n' => Key::Enter,
+            BACKSPACE | DELETE => Key::Backspace,
+            CTRL_C => Key::Interrupt,
+            // ANSI escape sequences sent by cursor keys
+            ESC if read_byte() == b'[' => match read_byte() {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                _ => Key::Ignored,
+            },
+            byte if byte.is_ascii_graphic() || byte == b' ' => Key::Char(byte),
+            _ => Key::Ignored,
+        }
+    }
+
+    fn redraw(&self) {
+        print!(
+            "\r\x1b[K{}{}",
+            PROMPT,
+            core::str::from_utf8(&self.line).unwrap_or("")
+        );
+
+        let back = self.line.len() - self.cursor;
+        if back > 0 {
+            print!("\x1b[{}D", back);
+        }
+    }
+
+    fn load_history(&mut self, idx: usize) {
+        self.history_idx = idx;
+        self.line = self
+            .history
+            .get(idx)
+            .map(|line| line.as_bytes().to_vec())
+            .unwrap_or_default();
+        self.cursor = self.line.len();
+        self.redraw();
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.history_idx = self.history.len();
+        print!("{}", PROMPT);
+    }
+
+    fn read_line(&mut self) -> String {
+        self.reset();
+
+        loop {
+            match self.read_key() {
+                Key::Char(c) if self.line.len() < MAX_LINE => {
+                    self.line.insert(self.cursor, c);
+                    self.cursor += 1;
+                    self.redraw();
+                }
+                Key::Backspace if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                    self.redraw();
+                }
+                Key::Left if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    print!("\x1b[D");
+                }
+                Key::Right if self.cursor < self.line.len() => {
+                    self.cursor += 1;
+                    print!("\x1b[C");
+                }
+                Key::Home => {
+                    self.cursor = 0;
+                    self.redraw();
+                }
+                Key::End => {
+                    self.cursor = self.line.len();
+                    self.redraw();
+                }
+                Key::Up if self.history_idx > 0 => self.load_history(self.history_idx - 1),
+                Key::Down if self.history_idx < self.history.len() => {
+                    self.load_history(self.history_idx + 1)
+                }
+                Key::Interrupt => {
+                    println!("^C");
+                    self.reset();
+                }
+                Key::Enter => break,
+                _ => {}
+            }
+        }
+        println!();
+
+        // Only printable ascii gets into the line
+        let line = String::from_utf8(core::mem::take(&mut self.line)).unwrap_or_default();
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            if self.history.len() == HISTORY_SIZE {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+
+        line
+    }
+}
+
+/// Kernel process running the shell on the console, has to be spawned with `spawn_process`
+pub unsafe extern "C" fn shell_task(_arg: u64) -> i64 {
+    TTY.set_mode(TtyMode::Raw);
+    println!("dotos shell, type `help` for the list of commands");
+
+    let mut editor = LineEditor::new();
+    loop {
+        let line = editor.read_line();
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        if let Some((name, args)) = words.split_first() {
+            commands::run(name, args);
+        }
+    }
+}
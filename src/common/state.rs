@@ -1,28 +1,37 @@
-use core::sync::atomic::{AtomicU8, Ordering};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 
 use num_derive::{FromPrimitive, ToPrimitive};
-use num_traits::{FromPrimitive, ToPrimitive};
+use num_traits::ToPrimitive;
 
 #[derive(FromPrimitive, ToPrimitive, PartialEq)]
 pub enum KernelState {
     Init,
     SingleCoreRun,
+    /// Secondary cores are up and running kernel code
+    MultiCoreRun,
 }
 
 pub struct KernelInitManager {
     state: AtomicU8,
+    /// Boot core included
+    cores_online: AtomicUsize,
 }
 
 impl KernelInitManager {
     pub const fn new() -> Self {
         Self {
             state: AtomicU8::default(),
+            cores_online: AtomicUsize::new(1),
         }
     }
 
-    pub(crate) fn is_init(&self) -> bool {
-        let state = self.state.load(Ordering::Acquire);
-        KernelState::from_u8(state).expect("KernelState::from_u8") == KernelState::Init
+    /// Called by each secondary core once it finishes its own initialization
+    pub fn core_online(&self) {
+        self.cores_online.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn cores_online(&self) -> usize {
+        self.cores_online.load(Ordering::Acquire)
     }
 
     pub fn transition(&self, from: KernelState, to: KernelState) {
@@ -2,4 +2,7 @@ use crate::common::state::KernelInitManager;
 pub use crate::{arch::arch_impl::statics::*, bsp::device::statics::*};
 
 pub static STATE_MANAGER: KernelInitManager = KernelInitManager::new();
-pub use crate::common::memory::mmu::mapping::KERNEL_MAPPING_RECORD;
+pub use crate::common::memory::{
+    frame_allocator::FRAME_ALLOCATOR,
+    mmu::mapping::KERNEL_MAPPING_RECORD,
+};
@@ -0,0 +1,85 @@
+use core::{convert::TryInto, fmt, str};
+
+use crate::bsp::device::memory;
+
+/// Table generated by build.rs, located in the image by `link.ld`. Layout, little endian:
+/// symbol count as u64, then per symbol u64 address, u32 name offset and u32 name length,
+/// sorted by address, followed by names.
+#[used]
+#[link_section = ".kernel_symbols"]
+static KERNEL_SYMBOLS_IMAGE: [u8; include_bytes!(env!("KERNEL_SYMBOLS_PATH")).len()] =
+    *include_bytes!(env!("KERNEL_SYMBOLS_PATH"));
+
+const HEADER_SIZE: usize = 8;
+const ENTRY_SIZE: usize = 16;
+
+/// Function symbols of the kernel, empty unless it was built with `KERNEL_SYMBOLS`
+#[derive(Copy, Clone)]
+pub struct SymbolTable {
+    image: &'static [u8],
+}
+
+/// Address printed as `symbol+offset` when the table knows its function
+pub struct Symbolized(pub usize);
+
+pub fn kernel_symbols() -> SymbolTable {
+    SymbolTable {
+        image: memory::kernel_symbols(),
+    }
+}
+
+impl SymbolTable {
+    fn read_u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.image.get(offset..offset + 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn read_u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.image.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn len(&self) -> usize {
+        self.read_u64(0).unwrap_or(0) as usize
+    }
+
+    fn addr(&self, idx: usize) -> Option<usize> {
+        self.read_u64(HEADER_SIZE + idx * ENTRY_SIZE)
+            .map(|addr| addr as usize)
+    }
+
+    fn name(&self, idx: usize) -> Option<&'static str> {
+        let entry = HEADER_SIZE + idx * ENTRY_SIZE;
+        let offset = self.read_u32(entry + 8)? as usize;
+        let len = self.read_u32(entry + 12)? as usize;
+        let names = HEADER_SIZE + self.len() * ENTRY_SIZE + offset;
+
+        str::from_utf8(self.image.get(names..names + len)?).ok()
+    }
+
+    /// Closest symbol at or below `addr` together with the offset from it
+    pub fn resolve(&self, addr: usize) -> Option<(&'static str, usize)> {
+        // Index of the first symbol above `addr`
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.addr(mid)? <= addr {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let idx = low.checked_sub(1)?;
+        Some((self.name(idx)?, addr - self.addr(idx)?))
+    }
+}
+
+impl fmt::Display for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match kernel_symbols().resolve(self.0) {
+            Some((name, offset)) => write!(f, "{:#018x} {}+{:#x}", self.0, name, offset),
+            None => write!(f, "{:#018x} <unknown>", self.0),
+        }
+    }
+}
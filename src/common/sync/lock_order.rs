@@ -0,0 +1,174 @@
+#[cfg(debug_assertions)]
+pub use self::checks::{acquire, assert_no_locks_held, forget, release};
+
+#[cfg(not(debug_assertions))]
+#[inline(always)]
+pub fn acquire(_lock: usize) {}
+
+#[cfg(not(debug_assertions))]
+#[inline(always)]
+pub fn release(_lock: usize) {}
+
+#[cfg(not(debug_assertions))]
+#[inline(always)]
+pub fn assert_no_locks_held() {}
+
+/// Deadlock detection for debug builds. Each core keeps a stack of locks it holds and every pair
+/// of locks taken one inside the other is remembered. Taking a lock the core already holds, or
+/// two locks in the order opposite to an earlier one, panics before the lock is touched.
+/// Locks are told apart by their addresses. They're all taken with IRQs masked, so per core
+/// stacks need no locking of their own.
+#[cfg(debug_assertions)]
+mod checks {
+    use core::cell::UnsafeCell;
+
+    use crate::{
+        arch::aarch64::cpu::registers::mpidr_el1::core_id_el1,
+        bsp::device::cpu::NUM_CORES,
+        common::sync::spin_lock::TicketLock,
+    };
+
+    const MAX_HELD: usize = 16;
+    /// Pairs beyond this aren't remembered, so their order goes unchecked
+    const MAX_ORDERED_PAIRS: usize = 256;
+
+    #[derive(Copy, Clone)]
+    struct HeldLocks {
+        locks: [usize; MAX_HELD],
+        len: usize,
+    }
+
+    struct OrderedPairs {
+        /// `(outer, inner)`, inner lock was taken while outer one was held
+        pairs: [(usize, usize); MAX_ORDERED_PAIRS],
+        len: usize,
+    }
+
+    struct LockOrder {
+        held: UnsafeCell<[HeldLocks; NUM_CORES]>,
+        pairs_lock: TicketLock,
+        pairs: UnsafeCell<OrderedPairs>,
+    }
+
+    unsafe impl Sync for LockOrder {}
+
+    static LOCK_ORDER: LockOrder = LockOrder {
+        held: UnsafeCell::new(
+            [HeldLocks {
+                locks: [0; MAX_HELD],
+                len: 0,
+            }; NUM_CORES],
+        ),
+        pairs_lock: TicketLock::new(),
+        pairs: UnsafeCell::new(OrderedPairs {
+            pairs: [(0, 0); MAX_ORDERED_PAIRS],
+            len: 0,
+        }),
+    };
+
+    impl HeldLocks {
+        fn as_slice(&self) -> &[usize] {
+            &self.locks[..self.len]
+        }
+    }
+
+    impl OrderedPairs {
+        fn contains(&self, pair: (usize, usize)) -> bool {
+            self.pairs[..self.len].contains(&pair)
+        }
+
+        fn insert(&mut self, pair: (usize, usize)) {
+            if self.len < MAX_ORDERED_PAIRS && !self.contains(pair) {
+                self.pairs[self.len] = pair;
+                self.len += 1;
+            }
+        }
+
+        fn remove_lock(&mut self, lock: usize) {
+            let mut idx = 0;
+            while idx < self.len {
+                let (outer, inner) = self.pairs[idx];
+                if outer == lock || inner == lock {
+                    self.len -= 1;
+                    self.pairs[idx] = self.pairs[self.len];
+                } else {
+                    idx += 1;
+                }
+            }
+        }
+    }
+
+    impl LockOrder {
+        /// Only valid with IRQs masked
+        #[allow(clippy::mut_from_ref)]
+        fn held(&self) -> &mut HeldLocks {
+            let core_id = unsafe { core_id_el1() } as usize;
+            unsafe { &mut (*self.held.get())[core_id] }
+        }
+
+        fn map_pairs<R>(&self, f: impl FnOnce(&mut OrderedPairs) -> R) -> R {
+            self.pairs_lock.lock();
+            let res = f(unsafe { &mut *self.pairs.get() });
+            self.pairs_lock.unlock();
+
+            res
+        }
+    }
+
+    pub fn acquire(lock: usize) {
+        let held = LOCK_ORDER.held();
+        if held.as_slice().contains(&lock) {
+            panic!("deadlock: lock {:#x} is already held by this core", lock);
+        }
+
+        let inverted = LOCK_ORDER.map_pairs(|pairs| {
+            let inverted = held
+                .as_slice()
+                .iter()
+                .copied()
+                .find(|&outer| pairs.contains((lock, outer)));
+            if inverted.is_none() {
+                held.as_slice()
+                    .iter()
+                    .for_each(|&outer| pairs.insert((outer, lock)));
+            }
+
+            inverted
+        });
+        if let Some(outer) = inverted {
+            panic!(
+                "possible deadlock: lock {:#x} taken while holding {:#x}, earlier they were taken \
+                 the other way around",
+                lock, outer
+            );
+        }
+
+        if held.len == MAX_HELD {
+            panic!("too many locks held by this core");
+        }
+        held.locks[held.len] = lock;
+        held.len += 1;
+    }
+
+    /// Locks don't have to be released in the order they were taken
+    pub fn release(lock: usize) {
+        let held = LOCK_ORDER.held();
+        if let Some(idx) = held.as_slice().iter().rposition(|&taken| taken == lock) {
+            held.locks.copy_within(idx + 1..held.len, idx);
+            held.len -= 1;
+        }
+    }
+
+    /// Task switched to could take a lock left held by the previous one and spin forever
+    pub fn assert_no_locks_held() {
+        let held = LOCK_ORDER.held();
+        if let Some(lock) = held.as_slice().first() {
+            panic!("deadlock: switching tasks while lock {:#x} is held", lock);
+        }
+    }
+
+    /// Address of a dropped lock may be reused by an unrelated one
+    pub fn forget(lock: usize) {
+        LOCK_ORDER.map_pairs(|pairs| pairs.remove_lock(lock))
+    }
+}
@@ -1,7 +1,11 @@
 pub use crate::common::sync::{
-    init_state_lock::{InitStateLock, ReadWriteLock},
-    irq_safe_null_lock::{IRQSafeNullLock, Mutex},
+    lock_order::assert_no_locks_held,
+    rw_spin_lock::{ReadWriteLock, RwSpinLock},
+    spin_lock::{IRQSpinLock, Mutex, SpinLock},
+    wait_queue::WaitQueue,
 };
 
-mod init_state_lock;
-mod irq_safe_null_lock;
+mod lock_order;
+mod rw_spin_lock;
+mod spin_lock;
+mod wait_queue;
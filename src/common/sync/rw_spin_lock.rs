@@ -0,0 +1,119 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    arch::aarch64::cpu::exception::asynchronous::{local_irq_restore, local_irq_save, mask_irq},
+    common::sync::lock_order,
+};
+
+pub trait ReadWriteLock {
+    type Data;
+    fn map_read<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Self::Data) -> R;
+    fn map_write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Data) -> R;
+}
+
+/// Set in `RwSpinLock::state` while a writer holds the lock, remaining bits count readers
+const WRITER: u32 = 1 << 31;
+
+/// Reader-writer spinlock masking IRQs while it's held. Waiting writers don't hold new readers
+/// back, so it suits data which is written rarely, like tables filled in during init.
+pub struct RwSpinLock<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T> Send for RwSpinLock<T> where T: Send {}
+unsafe impl<T> Sync for RwSpinLock<T> where T: Send + Sync {}
+
+impl<T> RwSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    fn addr(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    fn read_lock(&self) {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & WRITER == 0
+                && self
+                    .state
+                    .compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return;
+            }
+
+            spin_loop()
+        }
+    }
+
+    fn write_lock(&self) {
+        while self
+            .state
+            .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop()
+        }
+    }
+}
+
+impl<T> ReadWriteLock for RwSpinLock<T> {
+    type Data = T;
+
+    fn map_read<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Self::Data) -> R,
+    {
+        let state = local_irq_save();
+        mask_irq();
+        lock_order::acquire(self.addr());
+        self.read_lock();
+
+        let res = f(unsafe { &*self.data.get() });
+
+        self.state.fetch_sub(1, Ordering::Release);
+        lock_order::release(self.addr());
+        local_irq_restore(state);
+
+        res
+    }
+
+    fn map_write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Data) -> R,
+    {
+        let state = local_irq_save();
+        mask_irq();
+        lock_order::acquire(self.addr());
+        self.write_lock();
+
+        let res = f(unsafe { &mut *self.data.get() });
+
+        self.state.store(0, Ordering::Release);
+        lock_order::release(self.addr());
+        local_irq_restore(state);
+
+        res
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Drop for RwSpinLock<T> {
+    fn drop(&mut self) {
+        lock_order::forget(self.addr())
+    }
+}
@@ -0,0 +1,173 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    arch::aarch64::cpu::exception::asynchronous::{
+        is_irq_masked,
+        local_irq_restore,
+        local_irq_save,
+        mask_irq,
+    },
+    common::sync::lock_order,
+};
+
+pub trait Mutex {
+    type Data;
+    fn map_locked<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Data) -> R;
+}
+
+/// Cores get the lock in the order they started waiting for it. Without LSE, which cortex-a53
+/// doesn't have, atomic read-modify-writes compile to `ldaxr`/`stlxr` loops.
+pub(super) struct TicketLock {
+    next: AtomicU32,
+    serving: AtomicU32,
+}
+
+impl TicketLock {
+    pub(super) const fn new() -> Self {
+        Self {
+            next: AtomicU32::new(0),
+            serving: AtomicU32::new(0),
+        }
+    }
+
+    pub(super) fn lock(&self) {
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+
+        while self.serving.load(Ordering::Acquire) != ticket {
+            spin_loop()
+        }
+    }
+
+    /// Takes the lock only if nobody holds it or waits for it
+    pub(super) fn try_lock(&self) -> bool {
+        let serving = self.serving.load(Ordering::Relaxed);
+
+        self.next
+            .compare_exchange(
+                serving,
+                serving.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    pub(super) fn unlock(&self) {
+        self.serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Spinlock leaving IRQs as they are. Holder can't be interrupted or preempted while it spins on
+/// the same core, so it has to be taken with IRQs masked, `IRQSpinLock` does that on its own.
+pub struct SpinLock<T> {
+    lock: TicketLock,
+    data: UnsafeCell<T>,
+}
+
+/// Spinlock masking IRQs while it's held, so it protects data used by IRQ handlers as well
+pub struct IRQSpinLock<T> {
+    inner: SpinLock<T>,
+}
+
+unsafe impl<T> Send for SpinLock<T> where T: Send {}
+unsafe impl<T> Sync for SpinLock<T> where T: Send {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            lock: TicketLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    fn addr(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    /// Runs `f` only if the lock is free, for paths like panic which can't wait for it.
+    /// Lock order isn't checked, as it can't deadlock.
+    pub fn try_map_locked<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        if !self.lock.try_lock() {
+            return None;
+        }
+
+        let res = f(unsafe { &mut *self.data.get() });
+        self.lock.unlock();
+
+        Some(res)
+    }
+}
+
+impl<T> Mutex for SpinLock<T> {
+    type Data = T;
+
+    fn map_locked<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Data) -> R,
+    {
+        debug_assert!(is_irq_masked(), "SpinLock taken with IRQs unmasked");
+
+        lock_order::acquire(self.addr());
+        self.lock.lock();
+
+        let res = f(unsafe { &mut *self.data.get() });
+
+        self.lock.unlock();
+        lock_order::release(self.addr());
+
+        res
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Drop for SpinLock<T> {
+    fn drop(&mut self) {
+        lock_order::forget(self.addr())
+    }
+}
+
+impl<T> IRQSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: SpinLock::new(data),
+        }
+    }
+
+    /// See `SpinLock::try_map_locked`
+    pub fn try_map_locked<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let state = local_irq_save();
+        mask_irq();
+        let res = self.inner.try_map_locked(f);
+        local_irq_restore(state);
+
+        res
+    }
+}
+
+impl<T> Mutex for IRQSpinLock<T> {
+    type Data = T;
+
+    fn map_locked<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Data) -> R,
+    {
+        let state = local_irq_save();
+        mask_irq();
+        let res = self.inner.map_locked(f);
+        local_irq_restore(state);
+
+        res
+    }
+}
@@ -0,0 +1,84 @@
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::common::{
+    scheduler::SCHEDULER,
+    sync::{IRQSpinLock, Mutex},
+    task::TaskState,
+};
+
+/// Queue of tasks blocked until some event happens, tasks are woken in FIFO order
+pub struct WaitQueue {
+    waiters: IRQSpinLock<Vec<u64>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: IRQSpinLock::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn enqueue(&self, pid: u64) {
+        self.waiters.map_locked(|waiters| waiters.push(pid))
+    }
+
+    pub(crate) fn dequeue(&self) -> Option<u64> {
+        self.waiters.map_locked(|waiters| {
+            if waiters.is_empty() {
+                None
+            } else {
+                Some(waiters.remove(0))
+            }
+        })
+    }
+
+    pub(crate) fn dequeue_all(&self) -> Vec<u64> {
+        self.waiters.map_locked(mem::take)
+    }
+
+    /// Blocks current task until it gets woken up
+    pub fn wait(&self) {
+        SCHEDULER.block_current(TaskState::Blocked, |pid| {
+            self.enqueue(pid);
+            true
+        })
+    }
+
+    /// Blocks current task until `condition` holds. Condition is checked under the queue lock
+    /// right before the task is queued, so wake ups can't slip in between.
+    /// It must not use this queue.
+    pub fn wait_until<F>(&self, mut condition: F)
+    where
+        F: FnMut() -> bool,
+    {
+        while !condition() {
+            SCHEDULER.block_current(TaskState::Blocked, |pid| {
+                self.waiters.map_locked(|waiters| {
+                    if condition() {
+                        return false;
+                    }
+
+                    waiters.push(pid);
+                    true
+                })
+            })
+        }
+    }
+
+    /// Wakes longest waiting task, returns `false` if nobody was waiting
+    pub fn wake_one(&self) -> bool {
+        if let Some(pid) = self.dequeue() {
+            SCHEDULER.wake(pid);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn wake_all(&self) {
+        for pid in self.dequeue_all() {
+            SCHEDULER.wake(pid)
+        }
+    }
+}
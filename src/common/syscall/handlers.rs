@@ -0,0 +1,173 @@
+use core::{slice, str, time::Duration};
+
+use super::{Errno, SysCallArgs};
+use crate::{
+    bsp::device::memory::mmu::KernelGranule,
+    common::{
+        fs::{self, AccessMode, SeekFrom, PATH_MAX},
+        memory::Address,
+        scheduler::{spawn_user_process, task_exit, SCHEDULER},
+        statics::CLOCK_TIMER,
+        sync::Mutex,
+        time::{clock::ClockManager, timer_queue},
+    },
+};
+
+/// Fails unless `addr..addr + len` lies within memory mapped for the current task
+fn check_user_range(addr: usize, len: usize, write: bool, execute: bool) -> Result<(), Errno> {
+    if SCHEDULER.map_current(|task| task.mm.contains(addr, len, write, execute)) {
+        Ok(())
+    } else {
+        Err(Errno::EFAULT)
+    }
+}
+
+fn pages_for(len: usize) -> Result<usize, Errno> {
+    if len == 0 {
+        return Err(Errno::EINVAL);
+    }
+
+    len.checked_add(KernelGranule::MASK)
+        .map(|len| len >> KernelGranule::SHIFT)
+        .ok_or(Errno::EINVAL)
+}
+
+pub unsafe fn sys_exit(args: &SysCallArgs) -> Result<u64, Errno> {
+    task_exit(args[0] as i64)
+}
+
+pub unsafe fn sys_write(args: &SysCallArgs) -> Result<u64, Errno> {
+    let (fd, start, len) = (args[0] as usize, args[1] as usize, args[2] as usize);
+    let file = SCHEDULER.map_current(|task| task.files.get(fd))?;
+    if len == 0 {
+        return Ok(0);
+    }
+    check_user_range(start, len, false, false)?;
+
+    file.write(slice::from_raw_parts(start as *const u8, len))
+        .map(|written| written as u64)
+}
+
+pub unsafe fn sys_read(args: &SysCallArgs) -> Result<u64, Errno> {
+    let (fd, start, len) = (args[0] as usize, args[1] as usize, args[2] as usize);
+    let file = SCHEDULER.map_current(|task| task.files.get(fd))?;
+    if len == 0 {
+        return Ok(0);
+    }
+    check_user_range(start, len, true, false)?;
+
+    file.read(slice::from_raw_parts_mut(start as *mut u8, len))
+        .map(|read| read as u64)
+}
+
+pub unsafe fn sys_yield(_args: &SysCallArgs) -> Result<u64, Errno> {
+    SCHEDULER.yield_now();
+
+    Ok(0)
+}
+
+pub unsafe fn sys_sleep(args: &SysCallArgs) -> Result<u64, Errno> {
+    timer_queue::sleep(Duration::from_micros(args[0]));
+
+    Ok(0)
+}
+
+pub unsafe fn sys_getpid(_args: &SysCallArgs) -> Result<u64, Errno> {
+    Ok(SCHEDULER.current_pid())
+}
+
+pub unsafe fn sys_spawn(args: &SysCallArgs) -> Result<u64, Errno> {
+    let (entry, stack_top) = (args[0] as usize, args[1] as usize);
+    if entry % 4 != 0 || stack_top % 16 != 0 {
+        return Err(Errno::EINVAL);
+    }
+    check_user_range(entry, 4, false, true)?;
+    check_user_range(stack_top.wrapping_sub(16), 16, true, false)?;
+
+    let (mm, files) = SCHEDULER.map_current(|task| {
+        task.mm
+            .duplicate()
+            .map(|mm| (mm, task.files.clone()))
+            .map_err(|_| Errno::ENOMEM)
+    })?;
+
+    spawn_user_process(Address::new(entry), Address::new(stack_top), mm, files).map_err(|err| {
+        crate::warn!("spawn: {}", err);
+        Errno::ENOMEM
+    })
+}
+
+pub unsafe fn sys_wait(args: &SysCallArgs) -> Result<u64, Errno> {
+    let (pid, status) = (args[0], args[1] as usize);
+    if status != 0 {
+        check_user_range(status, 8, true, false)?;
+    }
+
+    let code = SCHEDULER.wait(pid).map_err(|_| Errno::ECHILD)?;
+    if status != 0 {
+        // Region could have been unmapped while the caller was blocked
+        check_user_range(status, 8, true, false)?;
+        (status as *mut i64).write_volatile(code);
+    }
+
+    Ok(pid)
+}
+
+pub unsafe fn sys_mmap(args: &SysCallArgs) -> Result<u64, Errno> {
+    let num_pages = pages_for(args[0] as usize)?;
+
+    SCHEDULER
+        .map_current(|task| task.mm.alloc_region(num_pages, true, false))
+        .map(|addr| addr.addr() as u64)
+        .map_err(|_| Errno::ENOMEM)
+}
+
+pub unsafe fn sys_munmap(args: &SysCallArgs) -> Result<u64, Errno> {
+    let start = Address::new(args[0] as usize);
+    let num_pages = pages_for(args[1] as usize)?;
+
+    SCHEDULER
+        .map_current(|task| task.mm.unmap_region(start, num_pages))
+        .map_err(|_| Errno::EINVAL)?;
+
+    Ok(0)
+}
+
+pub unsafe fn sys_uptime(_args: &SysCallArgs) -> Result<u64, Errno> {
+    Ok(CLOCK_TIMER.map_locked(|t| t.uptime()).as_micros() as u64)
+}
+
+pub unsafe fn sys_open(args: &SysCallArgs) -> Result<u64, Errno> {
+    let (start, len) = (args[0] as usize, args[1] as usize);
+    if len > PATH_MAX {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    check_user_range(start, len, false, false)?;
+
+    let path = str::from_utf8(slice::from_raw_parts(start as *const u8, len))
+        .map_err(|_| Errno::EINVAL)?;
+    let file = fs::open(path, AccessMode::from_flags(args[2])?)?;
+
+    SCHEDULER
+        .map_current(|task| task.files.insert(file))
+        .map(|fd| fd as u64)
+}
+
+pub unsafe fn sys_close(args: &SysCallArgs) -> Result<u64, Errno> {
+    SCHEDULER.map_current(|task| task.files.close(args[0] as usize))?;
+
+    Ok(0)
+}
+
+pub unsafe fn sys_lseek(args: &SysCallArgs) -> Result<u64, Errno> {
+    let file = SCHEDULER.map_current(|task| task.files.get(args[0] as usize))?;
+    let offset = args[1] as i64;
+    let pos = match args[2] {
+        0 if offset >= 0 => SeekFrom::Start(offset as usize),
+        1 => SeekFrom::Current(offset as isize),
+        2 => SeekFrom::End(offset as isize),
+        _ => return Err(Errno::EINVAL),
+    };
+
+    file.seek(pos).map(|pos| pos as u64)
+}
@@ -0,0 +1,103 @@
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+
+use crate::warn;
+
+mod handlers;
+
+/// Arguments passed in x0-x5 by the calling task
+pub type SysCallArgs = [u64; 6];
+
+type SysCallHandler = unsafe fn(&SysCallArgs) -> Result<u64, Errno>;
+
+/// Times are given in microseconds
+#[derive(Debug, Clone, Copy, FromPrimitive, ToPrimitive)]
+pub enum SysCall {
+    /// `exit(code) -> !`
+    Exit = 0,
+    /// `write(fd, buf, len) -> written`
+    Write = 1,
+    /// `read(fd, buf, len) -> read`, console returns early after a line end
+    Read = 2,
+    /// `yield() -> 0`
+    Yield = 3,
+    /// `sleep(us) -> 0`
+    Sleep = 4,
+    /// `getpid() -> pid`
+    GetPid = 5,
+    /// `spawn(entry, stack_top) -> pid`, child runs on a copy of caller's memory
+    Spawn = 6,
+    /// `wait(pid, status) -> pid`, exit code is stored at `status` unless it's null
+    Wait = 7,
+    /// `mmap(len) -> addr` of zeroed read-write pages, allocated on first access
+    Mmap = 8,
+    /// `munmap(addr, len) -> 0`, has to match a single `mmap` call
+    Munmap = 9,
+    /// `uptime() -> us`
+    Uptime = 10,
+    /// `open(path, path_len, flags) -> fd`, flags take `O_RDONLY`, `O_WRONLY` or `O_RDWR`
+    Open = 11,
+    /// `close(fd) -> 0`
+    Close = 12,
+    /// `lseek(fd, offset, whence) -> offset`, whence is `SEEK_SET`, `SEEK_CUR` or `SEEK_END`
+    Lseek = 13,
+}
+
+/// Error numbers returned to user space negated in x0, following linux convention
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(i64)]
+pub enum Errno {
+    ENOENT = 2,
+    EBADF = 9,
+    ECHILD = 10,
+    ENOMEM = 12,
+    EFAULT = 14,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    EMFILE = 24,
+    ESPIPE = 29,
+    EROFS = 30,
+    ENAMETOOLONG = 36,
+    ENOSYS = 38,
+}
+
+/// Indexed by `SysCall` discriminant
+static SYSCALL_TABLE: [SysCallHandler; 14] = [
+    handlers::sys_exit,
+    handlers::sys_write,
+    handlers::sys_read,
+    handlers::sys_yield,
+    handlers::sys_sleep,
+    handlers::sys_getpid,
+    handlers::sys_spawn,
+    handlers::sys_wait,
+    handlers::sys_mmap,
+    handlers::sys_munmap,
+    handlers::sys_uptime,
+    handlers::sys_open,
+    handlers::sys_close,
+    handlers::sys_lseek,
+];
+
+impl Errno {
+    pub fn as_return_value(self) -> u64 {
+        (-(self as i64)) as u64
+    }
+}
+
+/// Runs syscall `number` and returns value that should be stored in the caller's x0
+pub unsafe fn dispatch(number: u64, args: &SysCallArgs) -> u64 {
+    let result = match SysCall::from_u64(number) {
+        Some(call) => SYSCALL_TABLE[call as usize](args),
+        None => {
+            warn!("unknown syscall {}", number);
+            Err(Errno::ENOSYS)
+        }
+    };
+
+    match result {
+        Ok(value) => value,
+        Err(errno) => errno.as_return_value(),
+    }
+}
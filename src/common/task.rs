@@ -1,6 +1,9 @@
 use num_derive::{FromPrimitive, ToPrimitive};
 
-use crate::arch::arch_impl::task::{cpu_switch_to, CpuContext};
+use crate::{
+    arch::arch_impl::task::{cpu_switch_to, CpuContext},
+    common::{fs::file_table::FileTable, memory::user_space::UserAddressSpace},
+};
 
 #[derive(Default, Debug)]
 #[repr(C)]
@@ -13,12 +16,31 @@ pub struct Task {
     /// Task is performing critical work and cannot be dispossessed
     pub preempt_count: u64,
     pub stack: u64,
+    pub pid: u64,
+    /// Pid of the task which spawned this one, only the parent may wait for it
+    pub parent: u64,
+    /// Cores the task may run on, one bit per core
+    pub affinity: u64,
+    /// Set from the moment task gets picked until its context is saved after switching away,
+    /// other cores must not run, move or free it in the meantime
+    pub on_cpu: bool,
+    /// Value passed to `task_exit`, valid once task becomes a `Zombie`
+    pub exit_code: i64,
+    /// User memory of the task, empty for kernel tasks
+    pub mm: UserAddressSpace,
+    pub files: FileTable,
 }
 
 #[derive(FromPrimitive, ToPrimitive, Copy, Clone, Debug, PartialEq)]
 #[repr(C)]
 pub enum TaskState {
     Running = 0,
+    /// Task waits on a `WaitQueue` for an event
+    Blocked = 1,
+    /// Task waits for a timeout to pass
+    Sleeping = 2,
+    /// Task has exited and waits for its exit code to be collected
+    Zombie = 3,
 }
 
 impl Default for TaskState {
@@ -29,6 +51,7 @@ impl Default for TaskState {
 
 impl Task {
     pub unsafe fn cpu_switch_to(prev: &Task, next: &Task) {
+        next.mm.activate();
         cpu_switch_to(prev as *const _, next as *const _)
     }
 }
@@ -1,2 +1,3 @@
 pub mod clock;
 pub mod scheduling;
+pub mod timer_queue;
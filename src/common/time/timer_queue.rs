@@ -0,0 +1,69 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::common::{
+    scheduler::SCHEDULER,
+    statics::CLOCK_TIMER,
+    sync::{IRQSpinLock, Mutex},
+    task::TaskState,
+    time::{clock::ClockManager, scheduling::TickCallbackHandler},
+};
+
+pub static TIMER_QUEUE: TimerQueue = TimerQueue::new();
+
+struct TimerEntry {
+    deadline: Duration,
+    pid: u64,
+}
+
+/// Sleeping tasks ordered by their wake up deadline
+pub struct TimerQueue {
+    entries: IRQSpinLock<Vec<TimerEntry>>,
+}
+
+impl TimerQueue {
+    pub const fn new() -> Self {
+        Self {
+            entries: IRQSpinLock::new(Vec::new()),
+        }
+    }
+
+    fn insert(&self, deadline: Duration, pid: u64) {
+        self.entries.map_locked(|entries| {
+            let idx = entries.partition_point(|entry| entry.deadline <= deadline);
+            entries.insert(idx, TimerEntry { deadline, pid });
+        })
+    }
+}
+
+impl TickCallbackHandler for TimerQueue {
+    fn handle(&self) {
+        let now = CLOCK_TIMER.map_locked(|t| t.uptime());
+
+        // Tasks are woken one by one with the queue unlocked, waking takes run queue locks
+        let pop_expired = || {
+            self.entries.map_locked(|entries| match entries.first() {
+                Some(entry) if entry.deadline <= now => Some(entries.remove(0).pid),
+                _ => None,
+            })
+        };
+        while let Some(pid) = pop_expired() {
+            SCHEDULER.wake(pid);
+        }
+    }
+}
+
+/// Puts current task to sleep for at least `duration`, other tasks run in the meantime
+pub fn sleep(duration: Duration) {
+    if duration.is_zero() {
+        SCHEDULER.yield_now();
+        return;
+    }
+
+    let deadline = CLOCK_TIMER.map_locked(|t| t.uptime()) + duration;
+
+    SCHEDULER.block_current(TaskState::Sleeping, |pid| {
+        TIMER_QUEUE.insert(deadline, pid);
+        true
+    });
+}
@@ -0,0 +1,164 @@
+use alloc::vec::Vec;
+
+use crate::common::{
+    ring_buffer::RingBuffer,
+    serial_console::{Read, Write},
+    statics::CONSOLE,
+    sync::{IRQSpinLock, Mutex},
+};
+
+const MAX_LINE: usize = 256;
+const MAX_READY: usize = 1024;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// Line discipline in front of the serial console
+pub static TTY: Tty = Tty::new();
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TtyMode {
+    /// Input is echoed and edited until a line end, Ctrl-C drops the line, Ctrl-D ends input
+    Canonical,
+    /// Bytes reach readers unchanged and without echo
+    Raw,
+}
+
+struct LineDiscipline {
+    mode: TtyMode,
+    /// Line being edited, only used in canonical mode
+    line: Vec<u8>,
+    /// Input ready to be consumed by readers, bytes which don't fit are dropped
+    ready: RingBuffer<u8, MAX_READY>,
+    /// Ctrl-D on an empty line, next read returns end of file
+    eof: bool,
+}
+
+pub struct Tty {
+    inner: IRQSpinLock<LineDiscipline>,
+}
+
+fn echo(bytes: &[u8]) {
+    for &byte in bytes {
+        CONSOLE.write_char(byte as char);
+    }
+}
+
+impl LineDiscipline {
+    const fn new() -> Self {
+        Self {
+            mode: TtyMode::Canonical,
+            line: Vec::new(),
+            ready: RingBuffer::new(),
+            eof: false,
+        }
+    }
+
+    fn submit_line(&mut self) {
+        for byte in self.line.drain(..) {
+            let _ = self.ready.push(byte);
+        }
+    }
+
+    fn receive(&mut self, byte: u8) {
+        if self.mode == TtyMode::Raw {
+            let _ = self.ready.push(byte);
+            return;
+        }
+
+        match byte {
+            b'\r' | b'\n' => {
+                self.line.push(b'\n');
+                echo(b"\n");
+                self.submit_line();
+            }
+            BACKSPACE | DELETE => {
+                if self.line.pop().is_some() {
+                    echo(b"\x08 \x08");
+                }
+            }
+            CTRL_C => {
+                self.line.clear();
+                echo(b"^C\n");
+            }
+            CTRL_D if self.line.is_empty() => self.eof = true,
+            CTRL_D => self.submit_line(),
+            byte if self.line.len() < MAX_LINE => {
+                self.line.push(byte);
+                echo(&[byte]);
+            }
+            _ => {}
+        }
+    }
+
+    /// Canonical reads return at most a single line
+    fn take(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.ready.is_empty() {
+            return if self.eof {
+                self.eof = false;
+                Some(0)
+            } else {
+                None
+            };
+        }
+
+        let mut read = 0;
+        while read < buf.len() {
+            let byte = match self.ready.pop() {
+                Some(byte) => byte,
+                None => break,
+            };
+            buf[read] = byte;
+            read += 1;
+
+            if byte == b'\n' && self.mode == TtyMode::Canonical {
+                break;
+            }
+        }
+
+        Some(read)
+    }
+}
+
+impl Tty {
+    const fn new() -> Self {
+        Self {
+            inner: IRQSpinLock::new(LineDiscipline::new()),
+        }
+    }
+
+    /// Switching to raw mode hands the unfinished line over to readers
+    pub fn set_mode(&self, mode: TtyMode) {
+        self.inner.map_locked(|ld| {
+            if mode == TtyMode::Raw {
+                ld.submit_line();
+            }
+            ld.mode = mode;
+        })
+    }
+
+    /// Blocks until some input is available, returns 0 at end of file
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+
+        loop {
+            if let Some(read) = self.inner.map_locked(|ld| ld.take(buf)) {
+                return read;
+            }
+
+            // Console read blocks, so it can't happen with IRQs masked
+            let byte = CONSOLE.read_char() as u8;
+            self.inner.map_locked(|ld| ld.receive(byte));
+        }
+    }
+
+    pub fn write(&self, buf: &[u8]) -> usize {
+        echo(buf);
+
+        buf.len()
+    }
+}
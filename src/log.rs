@@ -1,24 +1,155 @@
-use core::{fmt, str::FromStr};
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::common::{
-    serial_console::Write,
-    statics::{CONSOLE, LOG_LEVEL},
+use crate::{
+    common::{
+        dmesg,
+        serial_console::Write,
+        statics::{CLOCK_TIMER, CONSOLE, LOG_LEVEL},
+        sync::{IRQSpinLock, Mutex},
+        time::clock::ClockManager,
+    },
+    println,
 };
 
+pub const MAX_LOG_LEVEL: usize = 4;
+
+struct ModuleFilter {
+    /// Module path without the crate name, e.g. `common::scheduler`
+    module: String,
+    level: usize,
+}
+
+static MODULE_FILTERS: IRQSpinLock<Vec<ModuleFilter>> = IRQSpinLock::new(Vec::new());
+/// Highest level enabled anywhere, lets disabled messages skip the filter lookup.
+/// Starts permissive, so messages logged before `init_logging` still see the global level.
+static MAX_ENABLED_LEVEL: AtomicUsize = AtomicUsize::new(MAX_LOG_LEVEL);
+
 pub unsafe fn init_logging() {
     if let Some(log_level) = option_env!("LOG_LEVEL")
         .map(usize::from_str)
         .transpose()
         .expect("parse LOG_LEVEL value")
     {
-        LOG_LEVEL = log_level;
+        set_log_level(log_level).expect("set LOG_LEVEL value");
+    }
+    update_max_enabled_level();
+}
+
+fn strip_crate_name(module_path: &str) -> &str {
+    module_path
+        .split_once("::")
+        .map_or("", |(_, module)| module)
+}
+
+fn update_max_enabled_level() {
+    let max = MODULE_FILTERS.map_locked(|filters| {
+        filters
+            .iter()
+            .map(|filter| filter.level)
+            .fold(log_level(), usize::max)
+    });
+    MAX_ENABLED_LEVEL.store(max, Ordering::Relaxed);
+}
+
+pub fn log_level() -> usize {
+    LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn set_log_level(level: usize) -> Result<(), &'static str> {
+    if level > MAX_LOG_LEVEL {
+        return Err("log level has to be between 0 and 4");
     }
+    LOG_LEVEL.store(level, Ordering::Relaxed);
+    update_max_enabled_level();
+
+    Ok(())
+}
+
+/// Overrides the level for `module` and its submodules, `None` removes the override
+pub fn set_module_log_level(module: &str, level: Option<usize>) -> Result<(), &'static str> {
+    let module = module.trim_matches(':');
+    if module.is_empty() {
+        return Err("empty module path");
+    }
+    if level.map_or(false, |level| level > MAX_LOG_LEVEL) {
+        return Err("log level has to be between 0 and 4");
+    }
+
+    MODULE_FILTERS.map_locked(|filters| {
+        filters.retain(|filter| filter.module != module);
+        if let Some(level) = level {
+            filters.push(ModuleFilter {
+                module: String::from(module),
+                level,
+            });
+        }
+    });
+    update_max_enabled_level();
+
+    Ok(())
+}
+
+/// Most specific filter matching `module_path` wins, global level applies otherwise
+pub fn enabled(module_path: &str, level: usize) -> bool {
+    if level > MAX_ENABLED_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    let module = strip_crate_name(module_path);
+    let filter_level = MODULE_FILTERS.map_locked(|filters| {
+        filters
+            .iter()
+            .filter(|filter| {
+                module
+                    .strip_prefix(filter.module.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|filter| filter.module.len())
+            .map(|filter| filter.level)
+    });
+
+    level <= filter_level.unwrap_or_else(log_level)
+}
+
+pub fn print_log_filters() {
+    println!("log level: {}", log_level());
+    MODULE_FILTERS.map_locked(|filters| {
+        for filter in filters.iter() {
+            println!("  {}: {}", filter.module, filter.level);
+        }
+    });
 }
 
 pub fn _print(args: fmt::Arguments) {
     CONSOLE.write_fmt(args).expect("default console write_fmt")
 }
 
+/// Prints the record on the console and keeps it in dmesg together with its module
+pub fn _log(log_kw: &str, module_path: &str, args: fmt::Arguments) {
+    let ts = CLOCK_TIMER.map_locked(|t| t.uptime());
+    let sts = ts.subsec_micros();
+    let (secs, millis, micros) = (ts.as_secs(), sts / 1000, sts % 1000);
+
+    _print(format_args!(
+        "({})[{:>3}.{:03}{:03}] {}\n",
+        log_kw, secs, millis, micros, args
+    ));
+    dmesg::record(format_args!(
+        "({})[{:>3}.{:03}{:03}] {}: {}\n",
+        log_kw,
+        secs,
+        millis,
+        micros,
+        strip_crate_name(module_path),
+        args
+    ));
+}
+
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => { $crate::log::_print(format_args!($($arg)*)) };
@@ -35,49 +166,14 @@ macro_rules! println {
 #[macro_export]
 macro_rules! log {
     ($log_kw:expr, $log_lv:expr, $s:expr) => {{
-        use crate::common::time::clock::ClockManager as _;
-        use crate::common::sync::Mutex;
-
-        let ts = $crate::common::statics::CLOCK_TIMER.map_locked(|t| t.uptime());
-        let sts = ts.subsec_micros();
-
-        #[allow(unused_unsafe)]
-        let global_log_level = unsafe { $crate::common::statics::LOG_LEVEL };
-
-            #[allow(unused_comparisons)]
-            if global_log_level >= $log_lv {
-            $crate::log::_print(
-                format_args_nl!(
-                    concat!("(", $log_kw, ")", "[{:>3}.{:03}{:03}] ", $s),
-                    ts.as_secs(),
-                    sts / 1000,
-                    sts % 1000
-                )
-            );
-        };
+        if $crate::log::enabled(module_path!(), $log_lv) {
+            $crate::log::_log($log_kw, module_path!(), format_args!($s));
+        }
     }};
     ($log_kw:expr, $log_lv:expr, $fs:expr, $($arg:tt)*) => {{
-        use crate::common::time::clock::ClockManager as _;
-        use crate::common::sync::Mutex;
-
-        let ts = $crate::common::statics::CLOCK_TIMER.map_locked(|t| t.uptime());
-        let sts = ts.subsec_micros();
-
-        #[allow(unused_unsafe)]
-        let global_log_level = unsafe { $crate::common::statics::LOG_LEVEL };
-
-        #[allow(unused_comparisons)]
-        if global_log_level >= $log_lv {
-            $crate::log::_print(
-                    format_args_nl!(
-                    concat!("(", $log_kw, ")", "[{:>3}.{:03}{:03}] ", $fs),
-                    ts.as_secs(),
-                    sts / 1000,
-                    sts % 1000,
-                    $($arg)*
-                )
-            );
-        };
+        if $crate::log::enabled(module_path!(), $log_lv) {
+            $crate::log::_log($log_kw, module_path!(), format_args!($fs, $($arg)*));
+        }
     }};
 }
 
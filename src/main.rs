@@ -16,9 +16,14 @@
 #![feature(const_maybe_uninit_write)]
 #![feature(once_cell)]
 #![feature(asm_const)]
+#![feature(alloc_error_handler)]
+
+extern crate alloc;
+
+use core::{hint::spin_loop, time::Duration};
 
 use arch::arch_impl::cpu::exception::current_privilege_level;
-use common::sync::ReadWriteLock;
+use common::sync::{Mutex, ReadWriteLock};
 
 use crate::{
     arch::arch_impl::cpu::{
@@ -26,16 +31,24 @@ use crate::{
             asynchronous::{unmask_irq, ExceptionStatus},
             init_exception_handling,
         },
-        park,
         registers::current_el::current_el,
+        secondary_core_entry,
     },
+    bsp::device::cpu::{start_secondary_cores, NUM_CORES},
     common::{
         driver::DriverManager,
-        memory::mmu::{map_kernel_binary, MemoryManagementUnit},
+        elf::loader::spawn_elf,
+        exception::asynchronous::{IPIManager, IRQDescriptor},
+        fs::{self, devfs, initramfs::initramfs},
+        memory::{
+            heap::{init_kernel_heap, KERNEL_HEAP},
+            mmu::{map_kernel_binary, MemoryManagementUnit},
+        },
         scheduler::{spawn_process, SCHEDULER},
+        shell::shell_task,
         state::KernelState,
         statics,
-        time::scheduling::SchedulingManager,
+        time::{clock::ClockManager, scheduling::SchedulingManager, timer_queue::TIMER_QUEUE},
     },
     log::init_logging,
 };
@@ -46,14 +59,19 @@ crate mod common;
 mod log;
 mod panic;
 
+/// Boot continues without cores which don't come online in time
+const SECONDARY_CORE_TIMEOUT: Duration = Duration::from_secs(1);
+
 unsafe fn kernel_init() -> ! {
     init_exception_handling();
 
     let kernel_addr = map_kernel_binary().expect("map kernel binary");
 
     statics::MMU
-        .enable_mmu_and_caching(kernel_addr)
-        .expect("mmu init");
+        .switch_kernel_tables(kernel_addr)
+        .expect("switch to kernel tables");
+
+    init_kernel_heap().expect("kernel heap init");
 
     statics::BSP_DRIVER_MANAGER
         .init_early_drivers()
@@ -68,19 +86,70 @@ unsafe fn kernel_init() -> ! {
         .register_irq_handlers()
         .expect("driver register_irq_handler");
 
+    statics::SYSTEM_TIMER_DRIVER
+        .register_handler(&TIMER_QUEUE)
+        .expect("register ticks for timer queue");
     statics::SYSTEM_TIMER_DRIVER
         .register_handler(&SCHEDULER)
         .expect("register ticks for scheduler");
+    statics::INTERRUPT_CONTROLLER
+        .register_ipi_handler(IRQDescriptor {
+            name: "scheduler",
+            handler: &SCHEDULER,
+        })
+        .expect("register IPI handler");
+    statics::INTERRUPT_CONTROLLER.enable_ipi();
+
+    let state = bring_up_secondary_cores();
 
     unmask_irq();
 
-    statics::STATE_MANAGER.transition(KernelState::Init, KernelState::SingleCoreRun);
+    statics::STATE_MANAGER.transition(KernelState::Init, state);
 
     init_logging();
 
     kernel_main()
 }
 
+unsafe fn bring_up_secondary_cores() -> KernelState {
+    start_secondary_cores(secondary_core_entry()).expect("start secondary cores");
+
+    let uptime = || statics::CLOCK_TIMER.map_locked(|t| t.uptime());
+    let deadline = uptime() + SECONDARY_CORE_TIMEOUT;
+    while statics::STATE_MANAGER.cores_online() < NUM_CORES && uptime() < deadline {
+        spin_loop()
+    }
+
+    let online = statics::STATE_MANAGER.cores_online();
+    if online < NUM_CORES {
+        warn!("only {} of {} cores came online", online, NUM_CORES);
+    }
+    if online > 1 {
+        KernelState::MultiCoreRun
+    } else {
+        KernelState::SingleCoreRun
+    }
+}
+
+/// Secondary cores continue here in the high half and start scheduling right away, with nothing
+/// queued yet they sit in the idle loop until tasks get spawned or balanced onto them
+unsafe fn secondary_core_init() -> ! {
+    init_exception_handling();
+
+    let kernel_addr = statics::KERNEL_TABLES.map_locked(|tables| tables.base_addr());
+    statics::MMU
+        .switch_kernel_tables(kernel_addr)
+        .expect("switch to kernel tables");
+
+    SCHEDULER.init();
+    statics::INTERRUPT_CONTROLLER.enable_ipi();
+    statics::STATE_MANAGER.core_online();
+
+    unmask_irq();
+
+    SCHEDULER.idle()
+}
+
 unsafe fn kernel_main() -> ! {
     info!(
         "{} - v{}",
@@ -93,19 +162,41 @@ unsafe fn kernel_main() -> ! {
     statics::BSP_DRIVER_MANAGER.print_status();
     statics::INTERRUPT_CONTROLLER.print_status();
     statics::KERNEL_MAPPING_RECORD.map_read(|r| r.print_status());
+    statics::FRAME_ALLOCATOR
+        .map_locked(|a| a.stats())
+        .print_status();
+    KERNEL_HEAP.print_status();
+    initramfs().print_status();
 
     info!("current privilege level: {}", current_privilege_level());
     info!("exception status: {}", ExceptionStatus::read());
 
     SCHEDULER.init();
 
-    spawn_process(kernel_proc as usize as u64, 0).expect("spawn test1 process");
-    loop {
-        SCHEDULER.schedule()
+    let pid = spawn_process(kernel_proc as usize as u64, 0).expect("spawn test1 process");
+    let code = SCHEDULER.wait(pid).expect("wait for test1 process");
+    info!("Kernel process {} exited with code {}", pid, code);
+
+    fs::init().expect("mount root filesystem");
+    statics::BSP_DRIVER_MANAGER
+        .publish_device_nodes()
+        .expect("publish device nodes");
+    devfs::print_status();
+
+    match initramfs().open("init") {
+        Ok(init) => {
+            spawn_elf(init.data, &["init"], &[]).expect("spawn init");
+        }
+        Err(_) => {
+            info!("no init in initramfs, starting kernel shell");
+            spawn_process(shell_task as usize as u64, 0).expect("spawn kernel shell");
+        }
     }
+
+    SCHEDULER.idle()
 }
 
-unsafe fn kernel_proc() {
+unsafe extern "C" fn kernel_proc(_arg: u64) -> i64 {
     crate::info!("Kernel process started {}", current_el());
-    park();
+    0
 }
@@ -1,6 +1,21 @@
-use core::{fmt, panic::PanicInfo};
+use core::{
+    alloc::Layout,
+    fmt,
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use crate::{arch::arch_impl::cpu, common::statics::panic_console};
+use crate::{
+    arch::arch_impl::cpu,
+    common::{backtrace::Backtrace, dmesg, statics::panic_console},
+};
+
+/// Amount of kernel log printed after panic message
+const PANIC_DMESG_TAIL: usize = 2048;
+
+/// Set by the first panic. Panic handler may panic itself, for example on a lock held by the
+/// panicking code, then the nested panic just stops the core.
+static PANICKED: AtomicBool = AtomicBool::new(false);
 
 fn panic_print(args: fmt::Arguments) {
     use fmt::Write;
@@ -18,11 +33,30 @@ macro_rules! panic_print {
 
 #[panic_handler]
 unsafe fn panic(info: &PanicInfo) -> ! {
+    if PANICKED.swap(true, Ordering::Relaxed) {
+        cpu::park()
+    }
+
     if let Some(args) = info.message() {
         panic_print!("\nKernel panic: {}", args);
     } else {
         panic_print!("\nKernel panic");
     }
+    panic_print!("\n{}", Backtrace::here());
+
+    panic_print!("\n\nLast kernel log records:\n");
+    let _ = dmesg::dump_tail(&mut panic_console(), PANIC_DMESG_TAIL);
 
     cpu::park()
 }
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    panic_print!(
+        "\nKernel heap exhausted: failed to allocate {} bytes aligned to {}",
+        layout.size(),
+        layout.align()
+    );
+
+    unsafe { cpu::park() }
+}